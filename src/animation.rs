@@ -0,0 +1,192 @@
+use atlas::{Atlas, Frame};
+use character::Character;
+use physics::{Position, Velocity};
+use push::{PushEvents, PushSystem};
+use quicksilver::{
+    geom::{Shape, Transform},
+    graphics::Background::Img,
+    lifecycle::Window,
+};
+use serde_json;
+use solver::Direction;
+use specs::{Component, HashMapStorage, Join, Read, ReadStorage, System, WriteStorage};
+
+/// How many ticks the character keeps pushing after the last push, so walking
+/// into a row of pushes doesn't flicker between walking and pushing.
+const PUSH_TICKS: u32 = 30;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Action {
+    Idle,
+    Walk,
+    Push,
+}
+
+/// The frames of one action facing one direction. Flipped frames are drawn
+/// mirrored horizontally, so one side can reuse the other side's frames.
+#[derive(Debug, Clone, Deserialize)]
+pub struct DirectionFrames {
+    pub frames: Vec<Frame>,
+    #[serde(default)]
+    pub flip: bool,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ActionFrames {
+    pub up: DirectionFrames,
+    pub down: DirectionFrames,
+    pub left: DirectionFrames,
+    pub right: DirectionFrames,
+}
+
+impl ActionFrames {
+    fn direction(&self, direction: Direction) -> &DirectionFrames {
+        match direction {
+            Direction::Up => &self.up,
+            Direction::Down => &self.down,
+            Direction::Left => &self.left,
+            Direction::Right => &self.right,
+        }
+    }
+}
+
+/// Describes where the character's frames are in its sprite sheet. The frames
+/// are relative to the sheet's image, wherever it was packed in the atlas.
+#[derive(Debug, Clone, Deserialize)]
+pub struct SpriteSheet {
+    pub image: String,
+    /// How many ticks each frame is shown for.
+    pub frame_delay: u32,
+    pub idle: ActionFrames,
+    pub walk: ActionFrames,
+    pub push: ActionFrames,
+}
+
+impl SpriteSheet {
+    /// The frame to show for the animation and whether it should be flipped.
+    pub fn frame(&self, animation: &CharacterAnimation) -> Option<(Frame, bool)> {
+        let action_frames = match animation.action {
+            Action::Idle => &self.idle,
+            Action::Walk => &self.walk,
+            Action::Push => &self.push,
+        };
+        let direction_frames = action_frames.direction(animation.direction);
+
+        if direction_frames.frames.is_empty() {
+            return None;
+        }
+
+        let index = (animation.ticks / self.frame_delay.max(1)) as usize;
+        Some((
+            direction_frames.frames[index % direction_frames.frames.len()],
+            direction_frames.flip,
+        ))
+    }
+
+    pub fn draw(
+        &self,
+        window: &mut Window,
+        atlas: &Atlas,
+        position: &Position,
+        animation: &CharacterAnimation,
+    ) {
+        let sprite = self.frame(animation).and_then(|(frame, flip)| {
+            atlas
+                .sprite_region(&self.image, &frame)
+                .map(|sprite| (sprite, flip))
+        });
+
+        if let Some((sprite, flip)) = sprite {
+            let transform = if flip {
+                Transform::scale((-1, 1))
+            } else {
+                Transform::IDENTITY
+            };
+
+            window.draw_ex(
+                &sprite.area().with_center(position.0),
+                Img(&sprite),
+                transform,
+                0,
+            );
+        }
+    }
+}
+
+pub fn parse_json(sprite_sheet_bytes: &[u8]) -> serde_json::Result<SpriteSheet> {
+    serde_json::from_slice(sprite_sheet_bytes)
+}
+
+/// What the character is doing and which way it's facing.
+#[derive(Debug)]
+pub struct CharacterAnimation {
+    pub action: Action,
+    pub direction: Direction,
+    /// Ticks since the action or the direction last changed.
+    pub ticks: u32,
+    push_ticks_left: u32,
+}
+
+impl Default for CharacterAnimation {
+    fn default() -> Self {
+        CharacterAnimation {
+            action: Action::Idle,
+            direction: Direction::Down,
+            ticks: 0,
+            push_ticks_left: 0,
+        }
+    }
+}
+
+impl CharacterAnimation {
+    fn transition(&mut self, action: Action, direction: Direction) {
+        if self.action != action || self.direction != direction {
+            debug!("Character animation: {:?} {:?}", action, direction);
+            self.action = action;
+            self.direction = direction;
+            self.ticks = 0;
+        } else {
+            self.ticks = self.ticks.wrapping_add(1);
+        }
+    }
+}
+
+impl Component for CharacterAnimation {
+    type Storage = HashMapStorage<Self>;
+}
+
+/// Picks the character's animation from its velocity and the pushes it makes.
+pub struct AnimationSystem;
+
+impl<'a> System<'a> for AnimationSystem {
+    type SystemData = (
+        Read<'a, PushEvents>,
+        ReadStorage<'a, Character>,
+        ReadStorage<'a, Velocity>,
+        WriteStorage<'a, CharacterAnimation>,
+    );
+
+    fn run(&mut self, (push_events, characters, velocities, mut animations): Self::SystemData) {
+        let pushed = push_events.0.last().map(|push_event| push_event.direction);
+
+        (&characters, &velocities, &mut animations).join().for_each(
+            |(_character, velocity, animation)| {
+                if pushed.is_some() {
+                    animation.push_ticks_left = PUSH_TICKS;
+                } else {
+                    animation.push_ticks_left = animation.push_ticks_left.saturating_sub(1);
+                }
+
+                let (action, direction) = match PushSystem::direction_from_velocity(velocity) {
+                    Some(direction) if animation.push_ticks_left > 0 => {
+                        (Action::Push, pushed.unwrap_or(direction))
+                    }
+                    Some(direction) => (Action::Walk, direction),
+                    None => (Action::Idle, animation.direction),
+                };
+
+                animation.transition(action, direction);
+            },
+        );
+    }
+}
@@ -0,0 +1,217 @@
+use animation::{self, SpriteSheet};
+use atlas::{self, Atlas, AtlasIndex};
+use futures::{future, Async};
+use map::{self, Stage};
+use quicksilver::{
+    graphics::{Font, Image},
+    load_file, Error as QuickSilverError, Future,
+};
+use std::fmt;
+use Settings;
+
+/// Every file the game loads before it starts, relative to `static`. The atlas
+/// index names the atlas image, which is loaded once the index is.
+#[derive(Debug, Clone)]
+pub struct AssetManifest {
+    pub font: String,
+    pub atlas: String,
+    pub character_sprites: String,
+    pub stages: String,
+}
+
+impl AssetManifest {
+    pub fn new(settings: &Settings) -> Self {
+        AssetManifest {
+            font: settings.mali_font_path.to_owned(),
+            atlas: settings.atlas_path.to_owned(),
+            character_sprites: settings.character_sprites_path.to_owned(),
+            stages: settings.stages_json_path.to_owned(),
+        }
+    }
+}
+
+/// A file that couldn't be loaded and why.
+#[derive(Debug, Clone)]
+pub struct LoadError {
+    pub path: String,
+    pub reason: String,
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Couldn't load {}: {}", self.path, self.reason)
+    }
+}
+
+/// The atlas texture along with the sprite sheets that point into it.
+pub struct Sprites {
+    pub atlas: Atlas,
+    pub character: SpriteSheet,
+}
+
+pub struct GameAsset {
+    pub mali_font: Font,
+    pub sprites: Sprites,
+}
+
+type AssetFuture<T> = Box<Future<Item = T, Error = QuickSilverError>>;
+
+/// One file that is being loaded.
+struct Pending<T> {
+    path: String,
+    future: AssetFuture<T>,
+    loaded: Option<T>,
+}
+
+impl<T> Pending<T> {
+    fn new(path: &str, future: AssetFuture<T>) -> Self {
+        Pending {
+            path: path.to_owned(),
+            future,
+            loaded: None,
+        }
+    }
+
+    /// Polls the file's future, returning whether it has been loaded.
+    fn poll(&mut self) -> Result<bool, LoadError> {
+        if self.loaded.is_some() {
+            return Ok(true);
+        }
+
+        match self.future.poll() {
+            Ok(Async::Ready(loaded)) => {
+                info!("Loaded {}", self.path);
+                self.loaded = Some(loaded);
+                Ok(true)
+            }
+            Ok(Async::NotReady) => Ok(false),
+            Err(err) => Err(LoadError {
+                path: self.path.to_owned(),
+                reason: format!("{}", err),
+            }),
+        }
+    }
+}
+
+fn parse_error(err: impl fmt::Display) -> QuickSilverError {
+    QuickSilverError::ContextError(format!("{}", err))
+}
+
+/// Loads every file in the manifest.
+pub struct AssetLoader {
+    font: Pending<Font>,
+    atlas_index: Pending<AtlasIndex>,
+    atlas_image: Option<Pending<Image>>,
+    character_sprites: Pending<SpriteSheet>,
+    stages: Pending<Vec<Stage>>,
+}
+
+impl AssetLoader {
+    pub fn new(manifest: &AssetManifest) -> Self {
+        info!("Loading assets: {:?}", manifest);
+
+        let atlas_index = load_file(manifest.atlas.to_owned()).and_then(|atlas_bytes| {
+            future::result(atlas::parse_json(&atlas_bytes).map_err(parse_error))
+        });
+        let character_sprites =
+            load_file(manifest.character_sprites.to_owned()).and_then(|sheet_bytes| {
+                future::result(animation::parse_json(&sheet_bytes).map_err(parse_error))
+            });
+        let stages = load_file(manifest.stages.to_owned()).and_then(|stages_bytes| {
+            future::result(map::load_stages(&stages_bytes).map_err(parse_error))
+        });
+
+        AssetLoader {
+            font: Pending::new(
+                &manifest.font,
+                Box::new(Font::load(manifest.font.to_owned())),
+            ),
+            atlas_index: Pending::new(&manifest.atlas, Box::new(atlas_index)),
+            atlas_image: None,
+            character_sprites: Pending::new(
+                &manifest.character_sprites,
+                Box::new(character_sprites),
+            ),
+            stages: Pending::new(&manifest.stages, Box::new(stages)),
+        }
+    }
+
+    /// The font, as soon as it's loaded, for the loading screen.
+    pub fn font(&self) -> Option<&Font> {
+        self.font.loaded.as_ref()
+    }
+
+    /// How many of the files have been loaded so far, out of how many.
+    pub fn progress(&self) -> (usize, usize) {
+        let loaded = [
+            self.font.loaded.is_some(),
+            self.atlas_index.loaded.is_some(),
+            self.atlas_image
+                .as_ref()
+                .map_or(false, |atlas_image| atlas_image.loaded.is_some()),
+            self.character_sprites.loaded.is_some(),
+            self.stages.loaded.is_some(),
+        ];
+
+        (
+            loaded.iter().filter(|loaded| **loaded).count(),
+            loaded.len(),
+        )
+    }
+
+    /// Polls every file, returning the assets and the stages once all of them
+    /// have been loaded.
+    pub fn poll(&mut self) -> Result<Option<(GameAsset, Vec<Stage>)>, LoadError> {
+        let is_font_loaded = self.font.poll()?;
+        let is_atlas_index_loaded = self.atlas_index.poll()?;
+        let is_character_loaded = self.character_sprites.poll()?;
+        let are_stages_loaded = self.stages.poll()?;
+
+        if self.atlas_image.is_none() {
+            if let Some(ref atlas_index) = self.atlas_index.loaded {
+                self.atlas_image = Some(Pending::new(
+                    &atlas_index.image,
+                    Box::new(Image::load(atlas_index.image.to_owned())),
+                ));
+            }
+        }
+        let is_atlas_image_loaded = match self.atlas_image {
+            Some(ref mut atlas_image) => atlas_image.poll()?,
+            None => false,
+        };
+
+        let is_loaded = is_font_loaded
+            && is_atlas_index_loaded
+            && is_atlas_image_loaded
+            && is_character_loaded
+            && are_stages_loaded;
+
+        if !is_loaded {
+            return Ok(None);
+        }
+
+        let loaded = (
+            self.font.loaded.take(),
+            self.atlas_index.loaded.take(),
+            self.atlas_image
+                .as_mut()
+                .and_then(|atlas_image| atlas_image.loaded.take()),
+            self.character_sprites.loaded.take(),
+            self.stages.loaded.take(),
+        );
+
+        match loaded {
+            (Some(mali_font), Some(index), Some(image), Some(character), Some(stages)) => {
+                let game_asset = GameAsset {
+                    mali_font,
+                    sprites: Sprites {
+                        atlas: Atlas { index, image },
+                        character,
+                    },
+                };
+                Ok(Some((game_asset, stages)))
+            }
+            _ => Ok(None),
+        }
+    }
+}
@@ -0,0 +1,60 @@
+use quicksilver::{geom::Rectangle, graphics::Image};
+use serde_json;
+use std::collections::BTreeMap;
+
+/// An area of an image, in pixels.
+#[derive(Debug, Clone, Copy, Deserialize, Serialize)]
+pub struct Frame {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+}
+
+impl Frame {
+    pub fn area(&self) -> Rectangle {
+        Rectangle::new((self.x, self.y), (self.width, self.height))
+    }
+}
+
+/// Where each of the packed images is in the atlas texture, by file name.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct AtlasIndex {
+    pub image: String,
+    pub frames: BTreeMap<String, Frame>,
+}
+
+pub fn parse_json(atlas_bytes: &[u8]) -> serde_json::Result<AtlasIndex> {
+    serde_json::from_slice(atlas_bytes)
+}
+
+/// The images in `static` packed into one texture by the `pack_atlas` binary.
+pub struct Atlas {
+    pub index: AtlasIndex,
+    pub image: Image,
+}
+
+impl Atlas {
+    /// The whole image that was packed with the given file name.
+    pub fn sprite(&self, name: &str) -> Option<Image> {
+        self.index
+            .frames
+            .get(name)
+            .map(|frame| self.image.subimage(frame.area()))
+    }
+
+    /// An area of the image that was packed with the given file name, e.g. one
+    /// frame of a sprite sheet.
+    pub fn sprite_region(&self, name: &str, region: &Frame) -> Option<Image> {
+        self.index.frames.get(name).map(|frame| {
+            self.image.subimage(
+                Frame {
+                    x: frame.x + region.x,
+                    y: frame.y + region.y,
+                    ..*region
+                }
+                .area(),
+            )
+        })
+    }
+}
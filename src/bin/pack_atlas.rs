@@ -0,0 +1,114 @@
+extern crate caudices;
+extern crate image;
+extern crate serde_json;
+
+use caudices::atlas::{AtlasIndex, Frame};
+use image::{GenericImage, RgbaImage};
+use std::{collections::BTreeMap, env, fs, path::Path, process};
+
+const ATLAS_NAME: &str = "atlas";
+const ATLAS_WIDTH: u32 = 1024;
+/// Space between the packed images so they don't bleed into each other.
+const PADDING: u32 = 1;
+
+/// Packs every png in the directory except the atlas itself into `atlas.png`
+/// and writes where each of them ended up to `atlas.json`. Run it with
+/// `cargo run --bin pack_atlas --features pack-atlas`.
+fn main() {
+    let path = env::args().nth(1).unwrap_or_else(|| "static".to_owned());
+
+    match pack(Path::new(&path)) {
+        Ok(count) => println!("Packed {} images into {}/{}.png", count, path, ATLAS_NAME),
+        Err(err) => {
+            eprintln!("Couldn't pack the atlas: {}", err);
+            process::exit(1);
+        }
+    }
+}
+
+fn read_sprites(dir: &Path) -> Result<Vec<(String, RgbaImage)>, String> {
+    let entries = fs::read_dir(dir).map_err(|err| format!("{}: {}", dir.display(), err))?;
+
+    entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| {
+            path.extension()
+                .map_or(false, |extension| extension == "png")
+        })
+        .filter(|path| path.file_stem().map_or(false, |stem| stem != ATLAS_NAME))
+        .map(|path| {
+            let name = path
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_default();
+
+            image::open(&path)
+                .map(|image| (name, image.to_rgba()))
+                .map_err(|err| format!("{}: {}", path.display(), err))
+        })
+        .collect()
+}
+
+/// Places the images in rows, tallest first, starting a new row when an image
+/// doesn't fit in the current one.
+fn place(sprites: &[(String, RgbaImage)]) -> Result<(BTreeMap<String, Frame>, u32), String> {
+    let mut frames = BTreeMap::new();
+    let (mut x, mut y, mut row_height) = (0, 0, 0);
+
+    for (name, sprite) in sprites {
+        if sprite.width() > ATLAS_WIDTH {
+            return Err(format!("{} is wider than the atlas", name));
+        }
+
+        if x + sprite.width() > ATLAS_WIDTH {
+            x = 0;
+            y += row_height + PADDING;
+            row_height = 0;
+        }
+
+        frames.insert(
+            name.to_owned(),
+            Frame {
+                x: x as f32,
+                y: y as f32,
+                width: sprite.width() as f32,
+                height: sprite.height() as f32,
+            },
+        );
+
+        x += sprite.width() + PADDING;
+        row_height = row_height.max(sprite.height());
+    }
+
+    Ok((frames, (y + row_height).next_power_of_two()))
+}
+
+fn pack(dir: &Path) -> Result<usize, String> {
+    let mut sprites = read_sprites(dir)?;
+    sprites.sort_by(|(a_name, a), (b_name, b)| {
+        b.height().cmp(&a.height()).then_with(|| a_name.cmp(b_name))
+    });
+
+    let (frames, height) = place(&sprites)?;
+
+    let mut atlas = RgbaImage::new(ATLAS_WIDTH, height);
+    sprites.iter().for_each(|(name, sprite)| {
+        let frame = &frames[name];
+        atlas.copy_from(sprite, frame.x as u32, frame.y as u32);
+    });
+
+    let image_name = format!("{}.png", ATLAS_NAME);
+    atlas
+        .save(dir.join(&image_name))
+        .map_err(|err| format!("{}: {}", image_name, err))?;
+
+    let index = AtlasIndex {
+        image: image_name,
+        frames,
+    };
+    let index_json = serde_json::to_string_pretty(&index).map_err(|err| err.to_string())?;
+    fs::write(dir.join(format!("{}.json", ATLAS_NAME)), index_json + "\n")
+        .map_err(|err| format!("{}.json: {}", ATLAS_NAME, err))?;
+
+    Ok(sprites.len())
+}
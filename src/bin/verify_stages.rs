@@ -0,0 +1,48 @@
+extern crate caudices;
+
+use caudices::{map, solver};
+use std::{env, fs, process};
+
+const MAX_STATES: usize = 1_000_000;
+
+fn main() {
+    let path = env::args()
+        .nth(1)
+        .unwrap_or_else(|| "static/stages.json".to_owned());
+
+    let stages_bytes = fs::read(&path).unwrap_or_else(|err| {
+        eprintln!("Couldn't read {}: {}", path, err);
+        process::exit(1);
+    });
+
+    let stages = map::load_stages(&stages_bytes).unwrap_or_else(|err| {
+        eprintln!("Couldn't load {}: {}", path, err);
+        process::exit(1);
+    });
+
+    let failures = stages
+        .iter()
+        .flat_map(|stage| stage.maps.iter().map(move |map| (stage.stage, map)))
+        .filter(|(stage, map)| match solver::solve_map(map, MAX_STATES) {
+            Ok(moves) => {
+                let pushes = moves.iter().filter(|solver_move| solver_move.push).count();
+                println!(
+                    "Stage {} level {}: solved in {} moves and {} pushes",
+                    stage,
+                    map.level,
+                    moves.len(),
+                    pushes
+                );
+                false
+            }
+            Err(err) => {
+                println!("Stage {} level {}: {}", stage, map.level, err);
+                true
+            }
+        })
+        .count();
+
+    if failures > 0 {
+        process::exit(1);
+    }
+}
@@ -1,4 +1,3 @@
-use quicksilver::geom::Rectangle;
 use specs::{Component, HashMapStorage};
 
 #[derive(Debug)]
@@ -7,12 +6,6 @@ pub struct Character {
     pub height: f32,
 }
 
-#[derive(Debug)]
-pub enum CharacterPosition {
-    Start(Rectangle),
-    Moving(Rectangle),
-}
-
 impl Default for Character {
     fn default() -> Self {
         Character {
@@ -0,0 +1,115 @@
+use collision::Collision;
+use map::{Collectible, CollectibleKind, CurrentLevel, Map};
+use ncollide2d::world::CollisionObjectHandle;
+use physics::PhysicsSystem;
+use score::Score;
+use specs::{Builder, Component, Entities, Join, ReadStorage, System, VecStorage, World, Write};
+
+/// The collision object of a collectible in the collision world.
+#[derive(Debug)]
+pub struct CollectibleHandle(pub CollisionObjectHandle);
+
+impl Component for CollectibleHandle {
+    type Storage = VecStorage<Self>;
+}
+
+/// The collision objects of the collectibles the character touched during the
+/// last dispatch.
+#[derive(Debug, Default)]
+pub struct Pickups(pub Vec<CollisionObjectHandle>);
+
+/// Replaces the collectibles of the previous level with the ones of the given
+/// map, along with their collision objects.
+pub fn create_collectibles(world: &mut World, map: &Map) {
+    {
+        let entities = world.entities();
+        let collectible_handles = world.read_storage::<CollectibleHandle>();
+        let mut collision = world.write_resource::<Collision>();
+
+        let handles = (&entities, &collectible_handles)
+            .join()
+            .map(|(entity, collectible_handle)| {
+                let _ = entities.delete(entity);
+                collectible_handle.0
+            })
+            .collect::<Vec<_>>();
+
+        if let Some(ref mut collision_world) = collision.world {
+            collision_world.remove(&handles);
+        }
+    }
+    world.maintain();
+    world.write_resource::<Pickups>().0.clear();
+
+    map.collectibles.iter().for_each(|collectible| {
+        let handle = PhysicsSystem::add_collectible_handle(
+            &mut world.write_resource::<Collision>(),
+            &collectible.position,
+        );
+        let builder = world.create_entity().with(collectible.to_owned());
+
+        match handle {
+            Some(handle) => builder.with(CollectibleHandle(handle)).build(),
+            None => builder.build(),
+        };
+    });
+}
+
+/// Picks up the collectibles the character touched, counting the gems and
+/// adding the time of the clocks to the level.
+pub struct CollectibleSystem;
+
+impl<'a> System<'a> for CollectibleSystem {
+    type SystemData = (
+        Entities<'a>,
+        ReadStorage<'a, Collectible>,
+        ReadStorage<'a, CollectibleHandle>,
+        Write<'a, Pickups>,
+        Write<'a, Collision>,
+        Write<'a, CurrentLevel>,
+        Write<'a, Score>,
+    );
+
+    fn run(
+        &mut self,
+        (
+            entities,
+            collectibles,
+            collectible_handles,
+            mut pickups,
+            mut collision,
+            mut current_level,
+            mut score,
+        ): Self::SystemData,
+    ) {
+        if pickups.0.is_empty() {
+            return;
+        }
+
+        (&entities, &collectibles, &collectible_handles)
+            .join()
+            .filter(|(_entity, _collectible, handle)| pickups.0.contains(&handle.0))
+            .for_each(|(entity, collectible, handle)| {
+                match collectible.kind {
+                    CollectibleKind::Gem => {
+                        info!("Picked up a gem");
+                        score.gems += 1;
+                    }
+                    CollectibleKind::Clock(bonus) => {
+                        info!("Picked up a clock with {}ms", bonus);
+                        score.bonus_time += bonus;
+                        if let Some(ref mut level) = current_level.0 {
+                            level.map.time += bonus;
+                        }
+                    }
+                }
+
+                if let Some(ref mut collision_world) = collision.world {
+                    collision_world.remove(&[handle.0]);
+                }
+                let _ = entities.delete(entity);
+            });
+
+        pickups.0.clear();
+    }
+}
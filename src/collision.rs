@@ -110,6 +110,14 @@ impl<'a> System<'a> for CollisionSystem {
     }
 }
 
+/// The collision object of a block in the collision world.
+#[derive(Debug)]
+pub struct BlockHandle(pub CollisionObjectHandle);
+
+impl Component for BlockHandle {
+    type Storage = VecStorage<Self>;
+}
+
 #[derive(Clone, Debug)]
 pub struct CollisionObjectData {
     pub name: &'static str,
@@ -0,0 +1,79 @@
+use character::Character;
+use floor::Floor;
+use map::{self, Block, CurrentLevel};
+use physics::Position;
+use push::PushEvents;
+use solver::Cell;
+use specs::{Read, ReadStorage, System, World, Write};
+use trigger::Triggers;
+
+/// The cells of the movable blocks that can't be pushed onto a goal anymore.
+#[derive(Debug, Default)]
+pub struct Deadlocks(pub Vec<Cell>);
+
+impl Deadlocks {
+    pub fn is_stuck(&self) -> bool {
+        !self.0.is_empty()
+    }
+}
+
+/// Checks the block layout for deadlocks after every push.
+pub struct DeadlockSystem;
+
+impl<'a> System<'a> for DeadlockSystem {
+    type SystemData = (
+        Read<'a, PushEvents>,
+        Read<'a, CurrentLevel>,
+        ReadStorage<'a, Character>,
+        ReadStorage<'a, Block>,
+        ReadStorage<'a, Position>,
+        Read<'a, Floor>,
+        Read<'a, Triggers>,
+        Write<'a, Deadlocks>,
+    );
+
+    fn run(
+        &mut self,
+        (
+            push_events,
+            current_level,
+            characters,
+            blocks,
+            positions,
+            floor,
+            triggers,
+            mut deadlocks,
+        ): Self::SystemData,
+    ) {
+        if push_events.0.is_empty() {
+            return;
+        }
+
+        if let Some(ref level) = current_level.0 {
+            if let Some(state) =
+                map::state_from_storages(&characters, &blocks, &positions, &floor, &triggers)
+            {
+                deadlocks.0 = level.board.deadlocked_blocks(&state);
+
+                if deadlocks.is_stuck() {
+                    warn!("Deadlocked blocks: {:?}", deadlocks.0);
+                }
+            }
+        }
+    }
+}
+
+/// Checks the current block layout for deadlocks, for when the blocks have been
+/// moved without a push, e.g. by undoing one.
+pub fn refresh(world: &World) {
+    let deadlocked_blocks = {
+        let current_level = world.read_resource::<CurrentLevel>();
+
+        match (&current_level.0, map::current_state(world)) {
+            (Some(level), Some(state)) => level.board.deadlocked_blocks(&state),
+            _ => vec![],
+        }
+    };
+
+    world.write_resource::<Deadlocks>().0 = deadlocked_blocks;
+}
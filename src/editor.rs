@@ -0,0 +1,563 @@
+use character::Character;
+use enemy::Enemy;
+use map::{
+    Block, BlockAndPosition, BlockKind, Collectible, CollectibleKind, Door, FloorKind, FloorTile,
+    Goal, Map, Patrol, Stage, Teleporter, Trigger, TriggerKind,
+};
+use nalgebra::Vector2;
+use quicksilver::{
+    geom::{Rectangle, Vector},
+    graphics::{Background::Col, Background::Img, Color, Font, FontStyle},
+    input::{ButtonState, Key, MouseButton},
+    lifecycle::Window,
+    Result,
+};
+use schema;
+use solver::Direction;
+use std::{fs, io};
+use utils::COLORS;
+use Position;
+use Settings;
+
+const GRID_SIZE: f32 = 50.;
+const TIME_CHANGE: u64 = 5000;
+/// The time the clocks placed in the editor add.
+const CLOCK_BONUS: u64 = 10000;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EditorTool {
+    Block(BlockKind),
+    Floor(FloorKind),
+    Door,
+    Trigger(TriggerKind),
+    Teleporter,
+    Enemy,
+    Collectible(CollectibleKind),
+    Start,
+    /// Whether the placed goals only take blocks of the current color.
+    Goal(bool),
+}
+
+#[derive(Debug, PartialEq)]
+pub enum EditorAction {
+    Edit,
+    TestPlay,
+    Save,
+    Exit,
+}
+
+#[derive(Debug)]
+pub struct Editor {
+    pub stage: u16,
+    pub map: Map,
+    pub tool: EditorTool,
+    pub color_index: usize,
+    /// The id that links the placed doors to their triggers and the placed
+    /// teleporters to each other.
+    pub link: u16,
+    /// The enemy whose path the placed waypoints are added to.
+    pub patrol: Option<usize>,
+}
+
+impl Editor {
+    pub fn new(stage: u16, map: Map) -> Self {
+        Editor {
+            stage,
+            map,
+            tool: EditorTool::Block(BlockKind::Fixed),
+            color_index: 0,
+            link: 0,
+            patrol: None,
+        }
+    }
+
+    pub fn snap_to_grid(point: Vector) -> Vector2<f32> {
+        Vector2::new(
+            (point.x / GRID_SIZE).round() * GRID_SIZE,
+            (point.y / GRID_SIZE).round() * GRID_SIZE,
+        )
+    }
+
+    fn color(&self) -> Color {
+        COLORS[self.color_index].1
+    }
+
+    fn is_at(block_with_position: &BlockAndPosition, position: &Vector2<f32>) -> bool {
+        block_with_position.position.0 == *position
+    }
+
+    /// Places the current tool at the given grid position. Blocks and doors
+    /// replace whatever block or door was in that cell before and goals are
+    /// toggled.
+    pub fn place(&mut self, position: Vector2<f32>) {
+        match self.tool {
+            EditorTool::Start => {
+                let is_occupied = self
+                    .map
+                    .blocks_with_position
+                    .iter()
+                    .any(|block_with_position| Self::is_at(block_with_position, &position));
+
+                if !is_occupied {
+                    self.map.start = Position(position);
+                }
+            }
+            EditorTool::Floor(kind) => {
+                self.map.floor.retain(|tile| tile.position.0 != position);
+                self.map.floor.push(FloorTile {
+                    kind,
+                    position: Position(position),
+                });
+            }
+            EditorTool::Door => {
+                if self.map.start.0 == position {
+                    return;
+                }
+
+                self.map
+                    .blocks_with_position
+                    .retain(|block_with_position| !Self::is_at(block_with_position, &position));
+                self.map.doors.retain(|door| door.position.0 != position);
+                self.map.doors.push(Door {
+                    id: self.link,
+                    position: Position(position),
+                });
+            }
+            EditorTool::Trigger(kind) => {
+                self.map
+                    .triggers
+                    .retain(|trigger| trigger.position.0 != position);
+                self.map.triggers.push(Trigger {
+                    kind,
+                    door: self.link,
+                    position: Position(position),
+                    active: false,
+                });
+            }
+            EditorTool::Enemy => {
+                let last_waypoint = self
+                    .patrol
+                    .and_then(|index| self.map.enemies.get(index))
+                    .and_then(|patrol| patrol.path.last())
+                    .map(|waypoint| waypoint.0);
+
+                match (self.patrol, last_waypoint) {
+                    // The path only goes in straight lines.
+                    (Some(index), Some(last))
+                        if last != position && (last.x == position.x || last.y == position.y) =>
+                    {
+                        self.map.enemies[index].path.push(Position(position));
+                    }
+                    (Some(_index), Some(_last)) => {}
+                    _ => {
+                        self.map.enemies.push(Patrol {
+                            path: vec![Position(position)],
+                            speed: 1.,
+                        });
+                        self.patrol = Some(self.map.enemies.len() - 1);
+                    }
+                }
+            }
+            EditorTool::Collectible(kind) => {
+                self.map
+                    .collectibles
+                    .retain(|collectible| collectible.position.0 != position);
+                self.map.collectibles.push(Collectible {
+                    kind,
+                    position: Position(position),
+                });
+            }
+            EditorTool::Teleporter => {
+                self.map
+                    .teleporters
+                    .retain(|teleporter| teleporter.position.0 != position);
+
+                // A third teleporter replaces the oldest of the pair.
+                let link = self.link;
+                let pads = self
+                    .map
+                    .teleporters
+                    .iter()
+                    .filter(|teleporter| teleporter.id == link)
+                    .count();
+                if pads >= 2 {
+                    if let Some(index) = self
+                        .map
+                        .teleporters
+                        .iter()
+                        .position(|teleporter| teleporter.id == link)
+                    {
+                        self.map.teleporters.remove(index);
+                    }
+                }
+
+                self.map.teleporters.push(Teleporter {
+                    id: link,
+                    position: Position(position),
+                });
+            }
+            EditorTool::Goal(is_colored) => {
+                let goal_count = self.map.goals.len();
+                self.map.goals.retain(|goal| goal.position.0 != position);
+
+                if self.map.goals.len() == goal_count {
+                    self.map.goals.push(Goal {
+                        position: Position(position),
+                        color: if is_colored { Some(self.color()) } else { None },
+                    });
+                }
+            }
+            EditorTool::Block(kind) => {
+                if self.map.start.0 == position {
+                    return;
+                }
+
+                self.map
+                    .blocks_with_position
+                    .retain(|block_with_position| !Self::is_at(block_with_position, &position));
+                self.map.doors.retain(|door| door.position.0 != position);
+                self.map.blocks_with_position.push(BlockAndPosition {
+                    block: Block {
+                        kind,
+                        color: self.color(),
+                        ..Block::default()
+                    },
+                    position: Position(position),
+                });
+            }
+        }
+    }
+
+    pub fn remove(&mut self, position: Vector2<f32>) {
+        self.map
+            .blocks_with_position
+            .retain(|block_with_position| !Self::is_at(block_with_position, &position));
+        self.map.goals.retain(|goal| goal.position.0 != position);
+        self.map.floor.retain(|tile| tile.position.0 != position);
+        self.map.doors.retain(|door| door.position.0 != position);
+        self.map
+            .triggers
+            .retain(|trigger| trigger.position.0 != position);
+        self.map
+            .teleporters
+            .retain(|teleporter| teleporter.position.0 != position);
+
+        self.map
+            .collectibles
+            .retain(|collectible| collectible.position.0 != position);
+
+        let enemy_count = self.map.enemies.len();
+        self.map
+            .enemies
+            .retain(|patrol| !patrol.path.iter().any(|waypoint| waypoint.0 == position));
+        if self.map.enemies.len() != enemy_count {
+            self.patrol = None;
+        }
+    }
+
+    /// Picks the one-way block tool, turning its direction if it's already
+    /// picked.
+    pub fn cycle_one_way(&mut self) {
+        let direction = match self.tool {
+            EditorTool::Block(BlockKind::OneWay(direction)) => {
+                let index = Direction::ALL
+                    .iter()
+                    .position(|one_way| *one_way == direction)
+                    .unwrap_or(0);
+                Direction::ALL[(index + 1) % Direction::ALL.len()]
+            }
+            _ => Direction::Up,
+        };
+
+        self.tool = EditorTool::Block(BlockKind::OneWay(direction));
+    }
+
+    /// Picks the next floor tool: ice, a conveyor in each direction and a hole.
+    pub fn cycle_floor(&mut self) {
+        let kind = match self.tool {
+            EditorTool::Floor(FloorKind::Ice) => FloorKind::Conveyor(Direction::ALL[0]),
+            EditorTool::Floor(FloorKind::Conveyor(direction)) => Direction::ALL
+                .iter()
+                .position(|conveyor| *conveyor == direction)
+                .and_then(|index| Direction::ALL.get(index + 1))
+                .map_or(FloorKind::Hole, |direction| FloorKind::Conveyor(*direction)),
+            _ => FloorKind::Ice,
+        };
+
+        self.tool = EditorTool::Floor(kind);
+    }
+
+    /// Picks the next trigger tool: a pressure plate, a switch and a key.
+    pub fn cycle_trigger(&mut self) {
+        let kind = match self.tool {
+            EditorTool::Trigger(TriggerKind::PressurePlate) => TriggerKind::Switch,
+            EditorTool::Trigger(TriggerKind::Switch) => TriggerKind::Key,
+            _ => TriggerKind::PressurePlate,
+        };
+
+        self.tool = EditorTool::Trigger(kind);
+    }
+
+    /// Picks the next goal tool: a goal for any block and one for the blocks
+    /// of the current color.
+    pub fn cycle_goal(&mut self) {
+        let is_colored = self.tool == EditorTool::Goal(false);
+        self.tool = EditorTool::Goal(is_colored);
+    }
+
+    /// Picks the next collectible tool: a gem and a clock.
+    pub fn cycle_collectible(&mut self) {
+        let kind = match self.tool {
+            EditorTool::Collectible(CollectibleKind::Gem) => CollectibleKind::Clock(CLOCK_BONUS),
+            _ => CollectibleKind::Gem,
+        };
+
+        self.tool = EditorTool::Collectible(kind);
+    }
+
+    /// Linked things are told apart by color, so there are as many ids as
+    /// colors.
+    pub fn cycle_link(&mut self) {
+        self.link = (self.link + 1) % COLORS.len() as u16;
+    }
+
+    pub fn cycle_color(&mut self) {
+        self.color_index = (self.color_index + 1) % COLORS.len();
+    }
+
+    pub fn change_time(&mut self, increase: bool) {
+        self.map.time = if increase {
+            self.map.time + TIME_CHANGE
+        } else {
+            self.map.time.saturating_sub(TIME_CHANGE).max(TIME_CHANGE)
+        };
+    }
+
+    pub fn handle_input(&mut self, settings: &Settings, window: &Window) -> EditorAction {
+        let is_pressed = |key: Key| window.keyboard()[key] == ButtonState::Pressed;
+
+        if is_pressed(Key::Key1) {
+            self.tool = EditorTool::Block(BlockKind::Fixed);
+        }
+        if is_pressed(Key::Key2) {
+            self.tool = EditorTool::Block(BlockKind::Movable);
+        }
+        if is_pressed(Key::Key3) {
+            self.tool = EditorTool::Start;
+        }
+        if is_pressed(Key::Key4) {
+            self.cycle_goal();
+        }
+        if is_pressed(Key::Key5) {
+            self.tool = EditorTool::Block(BlockKind::Ice);
+        }
+        if is_pressed(Key::Key6) {
+            self.tool = EditorTool::Block(BlockKind::Heavy);
+        }
+        if is_pressed(Key::Key7) {
+            self.tool = EditorTool::Block(BlockKind::Breakable);
+        }
+        if is_pressed(Key::Key8) {
+            self.cycle_one_way();
+        }
+        if is_pressed(Key::Key9) {
+            self.cycle_trigger();
+        }
+        if is_pressed(Key::D) {
+            self.tool = EditorTool::Door;
+        }
+        if is_pressed(Key::N) {
+            // Starts the path of a new enemy.
+            self.tool = EditorTool::Enemy;
+            self.patrol = None;
+        }
+        if is_pressed(Key::G) {
+            self.cycle_collectible();
+        }
+        if is_pressed(Key::O) {
+            self.tool = EditorTool::Teleporter;
+        }
+        if is_pressed(Key::I) {
+            self.cycle_link();
+        }
+        if is_pressed(Key::F) {
+            self.cycle_floor();
+        }
+        if is_pressed(Key::C) {
+            self.cycle_color();
+        }
+        if is_pressed(Key::X) {
+            self.map.clear_matches = !self.map.clear_matches;
+        }
+        if is_pressed(Key::V) {
+            self.map.gravity = !self.map.gravity;
+        }
+        if is_pressed(Key::Up) {
+            self.change_time(true);
+        }
+        if is_pressed(Key::Down) {
+            self.change_time(false);
+        }
+
+        let action = if is_pressed(Key::T) {
+            EditorAction::TestPlay
+        } else if is_pressed(Key::S) {
+            EditorAction::Save
+        } else if is_pressed(Key::Escape) {
+            EditorAction::Exit
+        } else {
+            EditorAction::Edit
+        };
+
+        let mouse = window.mouse();
+        let position = Self::snap_to_grid(mouse.pos());
+
+        if position.y >= settings.header_height {
+            if mouse[MouseButton::Left] == ButtonState::Pressed {
+                debug!("Editor placing {:?} at {:?}", self.tool, position);
+                self.place(position);
+            }
+            if mouse[MouseButton::Right] == ButtonState::Pressed {
+                debug!("Editor removing block at {:?}", position);
+                self.remove(position);
+            }
+        }
+
+        action
+    }
+
+    pub fn draw(&self, window: &mut Window, font: &Font) -> Result<()> {
+        let block_size = Vector::new(GRID_SIZE, GRID_SIZE);
+
+        self.map
+            .floor
+            .iter()
+            .for_each(|tile| tile.kind.draw(window, tile.position.0));
+        self.map
+            .teleporters
+            .iter()
+            .for_each(|teleporter| teleporter.draw(window));
+        self.map
+            .collectibles
+            .iter()
+            .for_each(|collectible| collectible.draw(window));
+        self.map.enemies.iter().for_each(|patrol| {
+            patrol.draw_path(window);
+            if let Some(start) = patrol.path.first() {
+                Enemy::draw(window, start);
+            }
+        });
+        self.map
+            .triggers
+            .iter()
+            .for_each(|trigger| trigger.draw(window));
+        self.map
+            .doors
+            .iter()
+            .for_each(|door| door.draw(window, false));
+
+        self.map
+            .blocks_with_position
+            .iter()
+            .for_each(|block_with_position| {
+                let position = block_with_position.position.0;
+                let block = &block_with_position.block;
+                let size = Vector::new(block.size.width, block.size.height);
+                let area = Rectangle::new_sized(size).with_center(position);
+
+                window.draw(&area, Col(block.color));
+                if block.kind == BlockKind::Fixed {
+                    // Fixed blocks get a dark center so they can be told apart.
+                    window.draw(
+                        &Rectangle::new_sized(size / 2.).with_center(position),
+                        Col(Color::BLACK),
+                    );
+                }
+                block.draw_marker(window, position);
+            });
+
+        self.map.goals.iter().for_each(|goal| {
+            let color = goal.color.unwrap_or(Color::WHITE);
+            window.draw(
+                &Rectangle::new_sized(block_size / 2.5).with_center(goal.position.0),
+                Col(color.with_alpha(0.5)),
+            );
+        });
+
+        let character = Character::default();
+        window.draw(
+            &Rectangle::new_sized(Vector::new(character.width, character.height) / 2.)
+                .with_center(self.map.start.0),
+            Col(Color::WHITE),
+        );
+
+        let cursor = Self::snap_to_grid(window.mouse().pos());
+        window.draw(
+            &Rectangle::new_sized(block_size / 4.).with_center(cursor),
+            Col(self.color().with_alpha(0.5)),
+        );
+
+        let tool_name = match self.tool {
+            EditorTool::Block(BlockKind::Fixed) => "Fixed".to_owned(),
+            EditorTool::Block(BlockKind::Movable) => "Movable".to_owned(),
+            EditorTool::Block(BlockKind::Ice) => "Ice".to_owned(),
+            EditorTool::Block(BlockKind::Heavy) => "Heavy".to_owned(),
+            EditorTool::Block(BlockKind::Breakable) => "Breakable".to_owned(),
+            EditorTool::Block(BlockKind::OneWay(direction)) => format!("One-way {:?}", direction),
+            EditorTool::Floor(FloorKind::Ice) => "Ice floor".to_owned(),
+            EditorTool::Floor(FloorKind::Conveyor(direction)) => {
+                format!("Conveyor {:?}", direction)
+            }
+            EditorTool::Floor(FloorKind::Hole) => "Hole".to_owned(),
+            EditorTool::Door => format!("Door {}", self.link),
+            EditorTool::Trigger(TriggerKind::PressurePlate) => format!("Plate {}", self.link),
+            EditorTool::Trigger(TriggerKind::Switch) => format!("Switch {}", self.link),
+            EditorTool::Trigger(TriggerKind::Key) => format!("Key {}", self.link),
+            EditorTool::Teleporter => format!("Teleporter {}", self.link),
+            EditorTool::Enemy => "Enemy".to_owned(),
+            EditorTool::Collectible(CollectibleKind::Gem) => "Gem".to_owned(),
+            EditorTool::Collectible(CollectibleKind::Clock(bonus)) => {
+                format!("Clock +{}s", bonus / 1000)
+            }
+            EditorTool::Start => "Start".to_owned(),
+            EditorTool::Goal(false) => "Goal".to_owned(),
+            EditorTool::Goal(true) => "Colored goal".to_owned(),
+        };
+        let header = format!(
+            "{} {} {}s{}{}",
+            tool_name,
+            COLORS[self.color_index].0,
+            self.map.time / 1000,
+            if self.map.clear_matches { " match" } else { "" },
+            if self.map.gravity { " gravity" } else { "" }
+        );
+        let font_style = FontStyle::new(32.0, Color::WHITE);
+
+        let _ = font.render(&header, &font_style).map(|text| {
+            window.draw(&text.area().with_center((200, 50)), Img(&text));
+        });
+        Ok(())
+    }
+}
+
+/// Writes all stages back to the stages file in the newest format, ordered by
+/// stage and level.
+pub fn save_stages(stages: &[Stage], path: &str) -> io::Result<()> {
+    let mut stages = stages.to_vec();
+    stages.sort_by_key(|stage| stage.stage);
+    stages
+        .iter_mut()
+        .for_each(|stage| stage.maps.sort_by_key(|map| map.level));
+
+    let json =
+        schema::to_json(stages).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+
+    if cfg!(target_arch = "wasm32") {
+        // There is no file system to write to on the web, so the stages are
+        // logged to be copied into the stages file.
+        info!("{}", json);
+        Ok(())
+    } else {
+        fs::write(path, json)
+    }
+}
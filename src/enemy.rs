@@ -0,0 +1,202 @@
+use character::Character;
+use floor::Floor;
+use map::{Block, CurrentLevel, Map, Patrol};
+use physics::{DeltaTime, Position};
+use quicksilver::{
+    geom::Circle,
+    graphics::{Background::Col, Color},
+    lifecycle::Window,
+};
+use solver::{Cell, GRID_SIZE};
+use specs::{
+    Builder, Component, Join, Read, ReadStorage, System, VecStorage, World, Write, WriteStorage,
+};
+use trigger::Triggers;
+
+const ENEMY_SIZE: f32 = 30.;
+
+/// An enemy walking its patrol, a cell at a time.
+#[derive(Debug, Clone)]
+pub struct Enemy {
+    pub path: Vec<Cell>,
+    /// In cells per second.
+    pub speed: f32,
+    /// The index of the waypoint it's walking to.
+    target: usize,
+    /// Whether it walks the path backwards, after running into something.
+    is_reversed: bool,
+    /// The cell it's walking into.
+    pub next: Cell,
+}
+
+impl Component for Enemy {
+    type Storage = VecStorage<Self>;
+}
+
+impl Enemy {
+    pub fn new(patrol: &Patrol) -> Self {
+        let path = patrol.cells();
+        let start = path[0];
+
+        Enemy {
+            target: 1 % path.len(),
+            path,
+            speed: patrol.speed,
+            is_reversed: false,
+            next: start,
+        }
+    }
+
+    /// The waypoint after the target one, in the direction it's walking.
+    fn advance(&mut self) {
+        let count = self.path.len();
+        self.target = if self.is_reversed {
+            (self.target + count - 1) % count
+        } else {
+            (self.target + 1) % count
+        };
+    }
+
+    /// Walks back to the waypoint it came from.
+    fn turn_around(&mut self) {
+        self.is_reversed = !self.is_reversed;
+        self.advance();
+    }
+
+    /// The cell after the given one on the way to the target waypoint, along the
+    /// row first.
+    fn step_from(&self, cell: Cell) -> Cell {
+        let target = self.path[self.target];
+
+        if cell.x != target.x {
+            Cell::new(cell.x + (target.x - cell.x).signum(), cell.y)
+        } else {
+            Cell::new(cell.x, cell.y + (target.y - cell.y).signum())
+        }
+    }
+
+    /// Whether it's in or walking into the given cell.
+    pub fn is_in(&self, position: &Position, cell: &Cell) -> bool {
+        Cell::from_position(&position.0) == *cell || self.next == *cell
+    }
+
+    pub fn draw(window: &mut Window, position: &Position) {
+        window.draw(&Circle::new(position.0, ENEMY_SIZE / 2.), Col(Color::RED));
+        window.draw(&Circle::new(position.0, ENEMY_SIZE / 6.), Col(Color::BLACK));
+    }
+}
+
+/// Whether an enemy has caught the character.
+#[derive(Debug, Default)]
+pub struct Caught(pub bool);
+
+/// Replaces the enemies of the previous level with the ones of the given map,
+/// each at the start of its path.
+pub fn create_enemies(world: &mut World, map: &Map) {
+    {
+        let entities = world.entities();
+        let enemies = world.read_storage::<Enemy>();
+        (&entities, &enemies).join().for_each(|(entity, _enemy)| {
+            let _ = entities.delete(entity);
+        });
+    }
+    world.maintain();
+
+    map.enemies.iter().for_each(|patrol| {
+        let enemy = Enemy::new(patrol);
+        let position = Position(enemy.next.to_position());
+        world.create_entity().with(enemy).with(position).build();
+    });
+    *world.write_resource::<Caught>() = Caught::default();
+}
+
+/// Walks the enemies along their paths. An enemy turns around when the next
+/// cell has a wall, a block, a closed door or a hole in it, and catches the
+/// character when they touch.
+pub struct EnemySystem;
+
+impl<'a> System<'a> for EnemySystem {
+    type SystemData = (
+        Read<'a, DeltaTime>,
+        Read<'a, CurrentLevel>,
+        Read<'a, Floor>,
+        Read<'a, Triggers>,
+        ReadStorage<'a, Character>,
+        ReadStorage<'a, Block>,
+        WriteStorage<'a, Enemy>,
+        WriteStorage<'a, Position>,
+        Write<'a, Caught>,
+    );
+
+    fn run(
+        &mut self,
+        (
+            delta,
+            current_level,
+            floor,
+            triggers,
+            characters,
+            blocks,
+            mut enemies,
+            mut positions,
+            mut caught,
+        ): Self::SystemData,
+    ) {
+        let board = match current_level.0 {
+            Some(ref level) => &level.board,
+            None => return,
+        };
+
+        let block_cells = (&blocks, &positions)
+            .join()
+            .map(|(_block, position)| Cell::from_position(&position.0))
+            .collect::<Vec<_>>();
+        let is_blocked = |cell: &Cell| {
+            !board.is_free(cell)
+                || triggers.is_closed(cell)
+                || floor.is_hole(cell)
+                || block_cells.contains(cell)
+        };
+
+        (&mut enemies, &mut positions)
+            .join()
+            .for_each(|(enemy, position)| {
+                let offset = enemy.next.to_position() - position.0;
+                let distance = enemy.speed * GRID_SIZE * delta.0;
+
+                if offset.norm() > distance {
+                    position.0 += offset.normalize() * distance;
+                    return;
+                }
+
+                position.0 = enemy.next.to_position();
+                let cell = enemy.next;
+                if cell == enemy.path[enemy.target] {
+                    enemy.advance();
+                }
+
+                let mut next = enemy.step_from(cell);
+                if next != cell && is_blocked(&next) {
+                    enemy.turn_around();
+                    next = enemy.step_from(cell);
+                }
+                if !is_blocked(&next) {
+                    enemy.next = next;
+                }
+            });
+
+        let character = (&characters, &positions).join().next();
+        if let Some((character, character_position)) = character {
+            let is_caught = (&enemies, &positions).join().any(|(_enemy, position)| {
+                let offset = position.0 - character_position.0;
+                offset.x.abs() < (character.width + ENEMY_SIZE) / 2. - 5.
+                    && offset.y.abs() < (character.height + ENEMY_SIZE) / 2. - 5.
+            });
+
+            if is_caught && !caught.0 {
+                info!("An enemy caught the character");
+                caught.0 = true;
+            }
+        }
+    }
+}
@@ -0,0 +1,191 @@
+use character::Character;
+use collision::{BlockHandle, Collision};
+use map::{Block, CurrentLevel, FloorKind, FloorTile, Map};
+use physics::{PhysicsSystem, Position, Velocity};
+use push::PushHistory;
+use solver::Cell;
+use specs::{Entities, Join, Read, ReadStorage, System, World, Write, WriteStorage};
+use std::collections::HashMap;
+use trigger::Triggers;
+
+/// How many ticks pass between the conveyors moving what stands on them.
+const CONVEYOR_INTERVAL: u32 = 30;
+
+/// The floor of the level that is being played. Holes that have been filled are
+/// plain floor.
+#[derive(Debug, Default, Clone)]
+pub struct Floor {
+    pub tiles: HashMap<Cell, FloorKind>,
+    /// Whether the character has fallen into a hole.
+    pub fell: bool,
+    ticks: u32,
+}
+
+impl Floor {
+    pub fn from_map(map: &Map) -> Self {
+        Floor {
+            tiles: map
+                .floor
+                .iter()
+                .map(|tile| (Cell::from_position(&tile.position.0), tile.kind))
+                .collect(),
+            ..Floor::default()
+        }
+    }
+
+    pub fn is_ice(&self, cell: &Cell) -> bool {
+        self.tiles.get(cell) == Some(&FloorKind::Ice)
+    }
+
+    pub fn is_hole(&self, cell: &Cell) -> bool {
+        self.tiles.get(cell) == Some(&FloorKind::Hole)
+    }
+
+    pub fn holes(&self) -> Vec<Cell> {
+        self.tiles
+            .iter()
+            .filter(|(_cell, kind)| **kind == FloorKind::Hole)
+            .map(|(cell, _kind)| *cell)
+            .collect()
+    }
+
+    /// The floor as it's written in the map files.
+    pub fn tiles(&self) -> Vec<FloorTile> {
+        self.tiles
+            .iter()
+            .map(|(cell, kind)| FloorTile {
+                kind: *kind,
+                position: Position(cell.to_position()),
+            })
+            .collect()
+    }
+}
+
+/// Whether the character is moving over ice, in which case it can't be steered.
+pub fn is_sliding(world: &World) -> bool {
+    let floor = world.read_resource::<Floor>();
+    let characters = world.read_storage::<Character>();
+    let velocities = world.read_storage::<Velocity>();
+    let positions = world.read_storage::<Position>();
+
+    (&characters, &velocities, &positions)
+        .join()
+        .any(|(_character, velocity, position)| {
+            (velocity.0.x != 0. || velocity.0.y != 0.)
+                && floor.is_ice(&Cell::from_position(&position.0))
+        })
+}
+
+/// Fills the holes with the blocks that are pushed into them, runs the conveyors
+/// and lets the character fall into the holes.
+pub struct FloorSystem;
+
+impl<'a> System<'a> for FloorSystem {
+    type SystemData = (
+        Entities<'a>,
+        Read<'a, CurrentLevel>,
+        Read<'a, Triggers>,
+        ReadStorage<'a, Character>,
+        ReadStorage<'a, Block>,
+        ReadStorage<'a, BlockHandle>,
+        WriteStorage<'a, Position>,
+        Write<'a, Collision>,
+        Write<'a, Floor>,
+        Write<'a, PushHistory>,
+    );
+
+    fn run(
+        &mut self,
+        (
+            entities,
+            current_level,
+            triggers,
+            characters,
+            blocks,
+            block_handles,
+            mut positions,
+            mut collision,
+            mut floor,
+            mut push_history,
+        ): Self::SystemData,
+    ) {
+        let board = match current_level.0 {
+            Some(ref level) => &level.board,
+            None => return,
+        };
+
+        let filling = (&entities, &blocks, &positions)
+            .join()
+            .filter(|(_entity, block, position)| {
+                block.kind.is_pushable() && floor.is_hole(&Cell::from_position(&position.0))
+            })
+            .map(|(entity, block, position)| (entity, block.to_owned(), position.to_owned()))
+            .collect::<Vec<_>>();
+
+        filling.into_iter().for_each(|(entity, block, position)| {
+            let cell = Cell::from_position(&position.0);
+            info!("Block {:?} filled the hole at {:?}", entity, cell);
+            floor.tiles.remove(&cell);
+
+            if let Some(block_handle) = block_handles.get(entity) {
+                if let Some(ref mut collision_world) = collision.world {
+                    collision_world.remove(&[block_handle.0]);
+                }
+            }
+            let _ = entities.delete(entity);
+            push_history.record_removed(entity, block);
+        });
+
+        floor.ticks = (floor.ticks + 1) % CONVEYOR_INTERVAL;
+        if floor.ticks == 0 {
+            let mut occupied = (&positions)
+                .join()
+                .map(|position| Cell::from_position(&position.0))
+                .collect::<Vec<_>>();
+            let conveyor_target = |cell: Cell| match floor.tiles.get(&cell) {
+                Some(FloorKind::Conveyor(direction)) => Some(cell.step(*direction)),
+                _ => None,
+            };
+
+            let moves = (&entities, &positions, (&blocks).maybe())
+                .join()
+                .filter(|(entity, _position, block)| match block {
+                    Some(block) => block.kind.is_pushable(),
+                    None => characters.get(*entity).is_some(),
+                })
+                .filter_map(|(entity, position, block)| {
+                    let target =
+                        conveyor_target(Cell::from_position(&position.0)).filter(|target| {
+                            board.is_free(target)
+                                && !triggers.is_closed(target)
+                                && !occupied.contains(target)
+                        })?;
+                    occupied.push(target);
+                    Some((entity, block.is_some(), target))
+                })
+                .collect::<Vec<_>>();
+
+            moves.into_iter().for_each(|(entity, is_block, target)| {
+                let position = Position(target.to_position());
+                if is_block {
+                    if let Some(block_handle) = block_handles.get(entity) {
+                        PhysicsSystem::set_block_handle_position(
+                            &mut collision,
+                            block_handle.0,
+                            &position,
+                        );
+                    }
+                }
+                let _ = positions.insert(entity, position);
+            });
+        }
+
+        let has_fallen = (&characters, &positions)
+            .join()
+            .any(|(_character, position)| floor.is_hole(&Cell::from_position(&position.0)));
+        if has_fallen && !floor.fell {
+            info!("The character fell into a hole");
+            floor.fell = true;
+        }
+    }
+}
@@ -0,0 +1,343 @@
+use animation::CharacterAnimation;
+use character::Character;
+use deadlock::{self, Deadlocks};
+use enemy::{Caught, Enemy};
+use floor::{self, Floor};
+use gravity::{Gravity, JUMP_SPEED};
+use hint::{self, Hints};
+use hud::Hud;
+use map::{self, Block, Collectible, CurrentLevel, Map};
+use nalgebra::Vector2;
+use physics::{Position, Velocity};
+use push::{self, PushEvents};
+use quicksilver::{
+    geom::{Rectangle, Shape, Vector},
+    graphics::{Background::Blended, Background::Col, Color, Image},
+    input::{ButtonState, Key},
+    lifecycle::Window,
+    Result,
+};
+use scene::{Scene, Transition};
+use scenes::{EditorScene, GameOverScene, LevelCompleteScene, LevelSelectScene, PauseScene};
+use solver;
+use specs::{Join, World};
+use std::time::Duration;
+use trigger::Triggers;
+use Game;
+use Settings;
+
+/// Plays the current level.
+pub struct GameplayScene {
+    hud: Hud,
+}
+
+impl GameplayScene {
+    pub fn new() -> Self {
+        GameplayScene { hud: Hud::new() }
+    }
+
+    fn draw_goals(window: &mut Window, map: &Map, goal_icon: Option<&Image>) {
+        map.goals.iter().for_each(|goal| {
+            let color = goal.color.unwrap_or(Color::WHITE).with_alpha(0.5);
+            match goal_icon {
+                Some(goal_icon) => window.draw(
+                    &goal_icon.area().with_center(goal.position.0),
+                    Blended(goal_icon, color),
+                ),
+                None => window.draw(
+                    &Rectangle::new_sized((20, 20)).with_center(goal.position.0),
+                    Col(color),
+                ),
+            }
+        });
+    }
+
+    fn draw_hint(window: &mut Window, hints: &Hints) {
+        if let Some(hint) = hints.current {
+            let cell_size = Vector::new(solver::GRID_SIZE, solver::GRID_SIZE);
+            let target = hint.target();
+
+            window.draw(
+                &Rectangle::new_sized(cell_size).with_center(target.to_position()),
+                Col(Color::YELLOW.with_alpha(0.4)),
+            );
+
+            if hint.solver_move.push {
+                let block_target = target.step(hint.solver_move.direction);
+                window.draw(
+                    &Rectangle::new_sized(cell_size).with_center(block_target.to_position()),
+                    Col(Color::GREEN.with_alpha(0.4)),
+                );
+            }
+        }
+    }
+
+    fn draw_deadlocks(window: &mut Window, deadlocks: &Deadlocks) {
+        let cell_size = Vector::new(solver::GRID_SIZE, solver::GRID_SIZE);
+
+        deadlocks.0.iter().for_each(|cell| {
+            window.draw(
+                &Rectangle::new_sized(cell_size).with_center(cell.to_position()),
+                Col(Color::RED.with_alpha(0.5)),
+            );
+        });
+    }
+
+    /// With gravity, Up jumps off whatever the character is standing on and
+    /// there's nothing to do with Down.
+    fn handle_keys_to_change_velocity(
+        settings: &Settings,
+        gravity: Option<&Gravity>,
+        velocity: &mut Velocity,
+        window: &mut Window,
+    ) {
+        let velocity_change = settings.velocity_change;
+
+        let mut key_match =
+            |key: Key,
+             on_press: Box<Fn(&mut Velocity) -> ()>,
+             on_release: Box<Fn(&mut Velocity) -> ()>| match window.keyboard()[key] {
+                ButtonState::Pressed | ButtonState::Held => {
+                    debug!("Key: {:?} pressed or held", key);
+
+                    on_press(velocity);
+                }
+                ButtonState::Released => {
+                    debug!("Key: {:?} released", key);
+
+                    on_release(velocity);
+                }
+                ButtonState::NotPressed => {}
+            };
+
+        match gravity {
+            Some(gravity) => {
+                if gravity.is_supported {
+                    key_match(
+                        Key::Up,
+                        Box::new(|velocity| velocity.0.y = -JUMP_SPEED),
+                        Box::new(|_velocity| {}),
+                    );
+                }
+            }
+            None => {
+                key_match(
+                    Key::Up,
+                    Box::new(move |velocity| velocity.0.y = -velocity_change),
+                    Box::new(|velocity| velocity.0.y = velocity.0.y.max(0.)),
+                );
+                key_match(
+                    Key::Down,
+                    Box::new(move |velocity| velocity.0.y = velocity_change),
+                    Box::new(|velocity| velocity.0.y = velocity.0.y.min(0.)),
+                );
+            }
+        }
+        key_match(
+            Key::Left,
+            Box::new(move |velocity| velocity.0.x = -velocity_change),
+            Box::new(|velocity| velocity.0.x = velocity.0.x.max(0.)),
+        );
+        key_match(
+            Key::Right,
+            Box::new(move |velocity| velocity.0.x = velocity_change),
+            Box::new(|velocity| velocity.0.x = velocity.0.x.min(0.)),
+        );
+    }
+
+    /// Stops the character, as the key releases aren't seen while another scene
+    /// is on top of this one.
+    fn stop_character(world: &World) {
+        let characters = world.read_storage::<Character>();
+        let mut velocities = world.write_storage::<Velocity>();
+
+        (&characters, &mut velocities)
+            .join()
+            .for_each(|(_character, velocity)| *velocity = Velocity(Vector2::new(0., 0.)));
+    }
+
+    fn push_scene(game: &mut Game, scene: Box<dyn Scene>) -> Result<Transition> {
+        Self::stop_character(&game.world);
+        Ok(Transition::Push(scene))
+    }
+}
+
+impl Scene for GameplayScene {
+    fn update(&mut self, game: &mut Game, window: &mut Window) -> Result<Transition> {
+        game.load_level()?;
+
+        let is_pressed = |window: &Window, key: Key| window.keyboard()[key] == ButtonState::Pressed;
+
+        if is_pressed(window, Key::Escape) || is_pressed(window, Key::P) {
+            return Self::push_scene(game, Box::new(PauseScene::new()));
+        }
+        if is_pressed(window, Key::M) {
+            let level_select = LevelSelectScene::new(game);
+            return Self::push_scene(game, Box::new(level_select));
+        }
+        if is_pressed(window, Key::E) {
+            if let Some(editor_scene) = EditorScene::for_current_map(game) {
+                return Self::push_scene(game, Box::new(editor_scene));
+            }
+        }
+
+        if is_pressed(window, Key::H) {
+            hint::request_hint(&game.world);
+        }
+        if is_pressed(window, Key::U) && push::undo(&mut game.world, &game.settings) {
+            deadlock::refresh(&game.world);
+            game.world.write_resource::<Hints>().current = None;
+        }
+        if is_pressed(window, Key::R) {
+            info!("Restarting level");
+            game.restart_level();
+        }
+        hint::clear_stale_hint(&game.world);
+
+        if map::is_level_complete(&game.world) {
+            info!("Level {} complete", game.current_level_id().level);
+            game.complete_level();
+            return Self::push_scene(game, Box::new(LevelCompleteScene::new()));
+        }
+
+        game.time_elapsed += Duration::from_millis(10);
+        game.world.maintain();
+        game.dispatcher.dispatch(&game.world.res);
+
+        if !game.world.read_resource::<PushEvents>().0.is_empty() {
+            game.save_progress();
+        }
+
+        let is_time_up = game
+            .world
+            .read_resource::<CurrentLevel>()
+            .0
+            .as_ref()
+            .map_or(false, |level| {
+                game.time_elapsed.as_secs() >= level.map.time / 1000
+            });
+
+        if is_time_up {
+            info!("Time is up");
+            return Self::push_scene(game, Box::new(GameOverScene::new("Time is up")));
+        }
+        if game.world.read_resource::<Floor>().fell {
+            return Self::push_scene(game, Box::new(GameOverScene::new("You fell into a hole")));
+        }
+        if game.world.read_resource::<Caught>().0 {
+            return Self::push_scene(game, Box::new(GameOverScene::new("An enemy caught you")));
+        }
+
+        if !floor::is_sliding(&game.world) {
+            let characters = game.world.read_storage::<Character>();
+            let mut velocity_storage = game.world.write_storage::<Velocity>();
+            let gravity = game.world.read_resource::<Gravity>();
+            let has_gravity = game
+                .world
+                .read_resource::<CurrentLevel>()
+                .0
+                .as_ref()
+                .map_or(false, |level| level.board.has_gravity);
+            let gravity = if has_gravity { Some(&*gravity) } else { None };
+
+            (&characters, &mut velocity_storage)
+                .join()
+                .for_each(|(_character, velocity)| {
+                    Self::handle_keys_to_change_velocity(&game.settings, gravity, velocity, window);
+                });
+        }
+
+        self.hud.update(game);
+
+        Ok(Transition::None)
+    }
+
+    fn draw(&mut self, game: &mut Game, window: &mut Window) -> Result<()> {
+        {
+            let world = &game.world;
+            let entities = world.entities();
+            let characters = world.read_storage::<Character>();
+            let animations = world.read_storage::<CharacterAnimation>();
+            let positions = world.read_storage::<Position>();
+            let blocks = world.read_storage::<Block>();
+            let enemies = world.read_storage::<Enemy>();
+            let collectibles = world.read_storage::<Collectible>();
+            let current_level = world.read_resource::<CurrentLevel>();
+            let hints = world.read_resource::<Hints>();
+            let deadlocks = world.read_resource::<Deadlocks>();
+            let floor = world.read_resource::<Floor>();
+            let triggers = world.read_resource::<Triggers>();
+
+            let settings = &game.settings;
+
+            let sprites = &game.game_asset.sprites;
+            let block_image = sprites.atlas.sprite(&settings.block_sprite);
+            let goal_icon = sprites.atlas.sprite(&settings.goal_sprite);
+
+            floor
+                .tiles
+                .iter()
+                .for_each(|(cell, kind)| kind.draw(window, cell.to_position()));
+            if let Some(ref level) = current_level.0 {
+                level
+                    .map
+                    .teleporters
+                    .iter()
+                    .for_each(|teleporter| teleporter.draw(window));
+            }
+            triggers
+                .triggers
+                .iter()
+                .for_each(|trigger| trigger.draw(window));
+            triggers
+                .doors
+                .iter()
+                .for_each(|door| door.door.draw(window, door.is_open));
+            (&collectibles)
+                .join()
+                .for_each(|collectible| collectible.draw(window));
+
+            (&entities, &positions)
+                .join()
+                .for_each(|(entity, position)| {
+                    if let (Some(_character), Some(animation)) =
+                        (characters.get(entity), animations.get(entity))
+                    {
+                        sprites
+                            .character
+                            .draw(window, &sprites.atlas, position, animation);
+                    }
+
+                    if let Some(block) = blocks.get(entity) {
+                        match block_image {
+                            Some(ref image) => window.draw(
+                                &image.area().with_center(position.0),
+                                Blended(image, block.color),
+                            ),
+                            None => window.draw(
+                                &Rectangle::new_sized((block.size.width, block.size.height))
+                                    .with_center(position.0),
+                                Col(block.color),
+                            ),
+                        }
+                        block.draw_marker(window, position.0);
+                    }
+
+                    if enemies.get(entity).is_some() {
+                        Enemy::draw(window, position);
+                    }
+                });
+
+            if let Some(ref level) = current_level.0 {
+                Self::draw_goals(window, &level.map, goal_icon.as_ref());
+            }
+
+            Self::draw_hint(window, &hints);
+            if settings.show_deadlocks {
+                Self::draw_deadlocks(window, &deadlocks);
+            }
+        }
+
+        self.hud.draw(window, game)
+    }
+}
@@ -0,0 +1,168 @@
+use character::Character;
+use collision::{BlockHandle, Collision};
+use map::{Block, CurrentLevel};
+use nalgebra::Vector2;
+use physics::{DeltaTime, PhysicsSystem, Position, Velocity};
+use solver::{Cell, Direction};
+use specs::{Entities, Join, Read, ReadStorage, System, Write, WriteStorage};
+use trigger::Triggers;
+
+/// In pixels per second squared.
+const GRAVITY: f32 = 1500.;
+/// In pixels per second.
+const MAX_FALL_SPEED: f32 = 600.;
+/// Enough to get the character onto a block that is one cell high.
+pub const JUMP_SPEED: f32 = 450.;
+/// How many updates it takes a block to fall a cell.
+const FALL_INTERVAL: u32 = 6;
+/// How far in from its sides the character has to be over something to stand
+/// on it.
+const FOOTING_MARGIN: f32 = 5.;
+/// How far above the cell it's landing in the character can be stopped, as the
+/// collision objects touch a little before the cells do.
+const LANDING_MARGIN: f32 = 4.;
+
+/// Whether the character is standing on something it can jump off, in the
+/// levels with gravity.
+#[derive(Debug, Default)]
+pub struct Gravity {
+    pub is_supported: bool,
+    ticks: u32,
+}
+
+/// Pulls the character down until it stands on a wall, a block or a closed door
+/// and drops the movable blocks a cell at a time onto whatever is below them,
+/// in the levels with gravity. Blocks that fall into a hole fill it.
+pub struct GravitySystem;
+
+impl GravitySystem {
+    /// The cells the character overlaps.
+    fn character_cells(character: &Character, position: &Position) -> Vec<Cell> {
+        let half_width = character.width / 2. - FOOTING_MARGIN;
+        let half_height = character.height / 2. - FOOTING_MARGIN;
+        let mut cells = [
+            (-half_width, -half_height),
+            (half_width, -half_height),
+            (-half_width, half_height),
+            (half_width, half_height),
+        ]
+        .iter()
+        .map(|(x, y)| Cell::from_position(&(position.0 + Vector2::new(*x, *y))))
+        .collect::<Vec<_>>();
+        cells.sort();
+        cells.dedup();
+        cells
+    }
+}
+
+impl<'a> System<'a> for GravitySystem {
+    type SystemData = (
+        Entities<'a>,
+        Read<'a, DeltaTime>,
+        Read<'a, CurrentLevel>,
+        Read<'a, Triggers>,
+        ReadStorage<'a, Character>,
+        ReadStorage<'a, Block>,
+        ReadStorage<'a, BlockHandle>,
+        WriteStorage<'a, Velocity>,
+        WriteStorage<'a, Position>,
+        Write<'a, Collision>,
+        Write<'a, Gravity>,
+    );
+
+    fn run(
+        &mut self,
+        (
+            entities,
+            delta,
+            current_level,
+            triggers,
+            characters,
+            blocks,
+            block_handles,
+            mut velocities,
+            mut positions,
+            mut collision,
+            mut gravity,
+        ): Self::SystemData,
+    ) {
+        let board = match current_level.0 {
+            Some(ref level) if level.board.has_gravity => &level.board,
+            _ => return,
+        };
+
+        let mut block_cells = (&blocks, &positions)
+            .join()
+            .map(|(_block, position)| Cell::from_position(&position.0))
+            .collect::<Vec<_>>();
+        let is_solid = |block_cells: &[Cell], cell: &Cell| {
+            !board.is_free(cell) || triggers.is_closed(cell) || block_cells.contains(cell)
+        };
+
+        let mut character_cells = vec![];
+        if let Some((character, velocity, position)) =
+            (&characters, &mut velocities, &mut positions).join().next()
+        {
+            let row_y = Cell::from_position(&position.0).to_position().y;
+            let half_width = character.width / 2. - FOOTING_MARGIN;
+            let columns = [position.0.x - half_width, position.0.x + half_width]
+                .iter()
+                .map(|x| Cell::from_position(&Vector2::new(*x, position.0.y)))
+                .collect::<Vec<_>>();
+            let is_on_ground = columns
+                .iter()
+                .any(|column| is_solid(&block_cells, &column.step(Direction::Down)));
+            let is_under_ceiling = columns
+                .iter()
+                .any(|column| is_solid(&block_cells, &column.step(Direction::Up)));
+
+            gravity.is_supported =
+                is_on_ground && velocity.0.y >= 0. && position.0.y >= row_y - LANDING_MARGIN;
+            if gravity.is_supported {
+                velocity.0.y = 0.;
+                position.0.y = row_y;
+            } else if is_under_ceiling && velocity.0.y < 0. && position.0.y <= row_y {
+                velocity.0.y = 0.;
+                position.0.y = row_y;
+            } else {
+                velocity.0.y = (velocity.0.y + GRAVITY * delta.0).min(MAX_FALL_SPEED);
+            }
+
+            character_cells = Self::character_cells(character, position);
+        }
+
+        gravity.ticks = (gravity.ticks + 1) % FALL_INTERVAL;
+        if gravity.ticks != 0 {
+            return;
+        }
+
+        // The lowest blocks fall first, so that the ones stacked on them follow
+        // in the same update.
+        let mut falling = (&entities, &blocks, &positions)
+            .join()
+            .filter(|(_entity, block, _position)| block.kind.is_pushable())
+            .map(|(entity, _block, position)| (entity, Cell::from_position(&position.0)))
+            .collect::<Vec<_>>();
+        falling.sort_by_key(|(_entity, cell)| -cell.y);
+
+        falling.into_iter().for_each(|(entity, cell)| {
+            let below = cell.step(Direction::Down);
+            if is_solid(&block_cells, &below) || character_cells.contains(&below) {
+                return;
+            }
+
+            debug!("Block {:?} falls from {:?} to {:?}", entity, cell, below);
+            if let Some(index) = block_cells
+                .iter()
+                .position(|block_cell| *block_cell == cell)
+            {
+                block_cells[index] = below;
+            }
+            let position = Position(below.to_position());
+            if let Some(block_handle) = block_handles.get(entity) {
+                PhysicsSystem::set_block_handle_position(&mut collision, block_handle.0, &position);
+            }
+            let _ = positions.insert(entity, position);
+        });
+    }
+}
@@ -0,0 +1,76 @@
+use map::{self, CurrentLevel};
+use solver::{self, Cell, Move};
+use specs::World;
+
+const MAX_HINT_STATES: usize = 100_000;
+
+/// The next recommended move, valid as long as the character stays in the cell
+/// it was in when the hint was asked for.
+#[derive(Debug, Clone, Copy)]
+pub struct Hint {
+    pub from: Cell,
+    pub solver_move: Move,
+}
+
+impl Hint {
+    pub fn target(&self) -> Cell {
+        self.from.step(self.solver_move.direction)
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Hints {
+    pub used: u16,
+    pub current: Option<Hint>,
+}
+
+/// Solves the level from where the character and the blocks are right now and
+/// shows the first move of the solution.
+pub fn request_hint(world: &World) {
+    let state = match map::current_state(world) {
+        Some(state) => state,
+        None => return,
+    };
+    let mut hints = world.write_resource::<Hints>();
+
+    if hints
+        .current
+        .map_or(false, |hint| hint.from == state.character)
+    {
+        return;
+    }
+
+    let current_level = world.read_resource::<CurrentLevel>();
+    let level = match current_level.0 {
+        Some(ref level) => level,
+        None => return,
+    };
+
+    match solver::solve(&level.board, &state, MAX_HINT_STATES) {
+        Ok(moves) => {
+            if let Some(solver_move) = moves.first() {
+                info!("Hint: {:?}", solver_move);
+                hints.used += 1;
+                hints.current = Some(Hint {
+                    from: state.character,
+                    solver_move: *solver_move,
+                });
+            }
+        }
+        Err(err) => warn!("No hint available: {}", err),
+    }
+}
+
+/// Removes the hint once the character has moved away from where it was given.
+pub fn clear_stale_hint(world: &World) {
+    let character = map::current_state(world).map(|state| state.character);
+    let mut hints = world.write_resource::<Hints>();
+
+    let is_stale = hints
+        .current
+        .map_or(false, |hint| Some(hint.from) != character);
+
+    if is_stale {
+        hints.current = None;
+    }
+}
@@ -0,0 +1,231 @@
+use deadlock::Deadlocks;
+use hint::Hints;
+use map::CurrentLevel;
+use quicksilver::{
+    geom::{Rectangle, Vector},
+    graphics::{Background::Img, Color, Font, FontStyle, Image},
+    lifecycle::Window,
+    Result,
+};
+use score::Score;
+use Game;
+use WINDOW_HEIGHT;
+use WINDOW_WIDTH;
+
+/// Space between the widgets and the sides of the window.
+const MARGIN: f32 = 10.;
+
+/// Text that is only rendered again when it changes.
+pub struct CachedText {
+    text: String,
+    style: FontStyle,
+    image: Option<Image>,
+}
+
+impl CachedText {
+    pub fn new(text: &str, style: FontStyle) -> Self {
+        CachedText {
+            text: text.to_owned(),
+            style,
+            image: None,
+        }
+    }
+
+    pub fn set_text(&mut self, text: &str) {
+        if self.text != text {
+            self.text = text.to_owned();
+            self.image = None;
+        }
+    }
+
+    /// Draws the text centered where `center` puts it, given the area of the
+    /// rendered text.
+    pub fn draw<F>(&mut self, window: &mut Window, font: &Font, center: F) -> Result<()>
+    where
+        F: Fn(&Rectangle) -> Vector,
+    {
+        if self.text.is_empty() {
+            return Ok(());
+        }
+
+        if self.image.is_none() {
+            self.image = font.render(&self.text, &self.style).ok();
+        }
+
+        if let Some(ref image) = self.image {
+            let area = image.area();
+            window.draw(&area.with_center(center(&area)), Img(image));
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum Anchor {
+    Left,
+    Center,
+    Right,
+}
+
+/// A line of text placed in the header.
+pub struct Widget {
+    anchor: Anchor,
+    /// How far down the header the text is centered, as a fraction of its height.
+    height: f32,
+    text: CachedText,
+}
+
+impl Widget {
+    pub fn new(anchor: Anchor, height: f32, style: FontStyle) -> Self {
+        Widget {
+            anchor,
+            height,
+            text: CachedText::new("", style),
+        }
+    }
+
+    pub fn set_text(&mut self, text: &str) {
+        self.text.set_text(text);
+    }
+
+    pub fn draw(&mut self, window: &mut Window, header_height: f32, font: &Font) -> Result<()> {
+        let anchor = self.anchor;
+        let y = header_height * self.height;
+
+        self.text.draw(window, font, |area| {
+            let x = match anchor {
+                Anchor::Left => MARGIN + area.size.x / 2.,
+                Anchor::Center => f32::from(WINDOW_WIDTH) / 2.,
+                Anchor::Right => f32::from(WINDOW_WIDTH) - MARGIN - area.size.x / 2.,
+            };
+            Vector::new(x, y)
+        })
+    }
+}
+
+/// The timer, level name, moves and hints shown above the level.
+pub struct Hud {
+    timer: Widget,
+    level: Widget,
+    moves: Widget,
+    hints: Widget,
+    warning: Widget,
+}
+
+impl Hud {
+    pub fn new() -> Self {
+        let text_style = FontStyle::new(24.0, Color::WHITE);
+
+        Hud {
+            timer: Widget::new(Anchor::Left, 0.5, FontStyle::new(72.0, Color::WHITE)),
+            level: Widget::new(Anchor::Center, 0.3, text_style),
+            moves: Widget::new(Anchor::Right, 0.3, text_style),
+            hints: Widget::new(Anchor::Right, 0.75, text_style),
+            warning: Widget::new(Anchor::Center, 0.75, FontStyle::new(24.0, Color::RED)),
+        }
+    }
+
+    /// Updates the widgets' text, which only renders them again if it changed.
+    pub fn update(&mut self, game: &Game) {
+        let current_level = game.world.read_resource::<CurrentLevel>();
+        let score = game.world.read_resource::<Score>();
+        let hints = game.world.read_resource::<Hints>();
+        let deadlocks = game.world.read_resource::<Deadlocks>();
+
+        let (time_left, level_name) = match current_level.0 {
+            Some(ref level) => {
+                let map_time = level.map.time / 1000;
+                let time_elapsed = game.time_elapsed.as_secs();
+                let time_left = if time_elapsed <= map_time {
+                    format!("{}", map_time - time_elapsed)
+                } else {
+                    "".to_owned()
+                };
+
+                (
+                    time_left,
+                    format!("Stage {} - Level {}", level.stage, level.map.level),
+                )
+            }
+            None => ("".to_owned(), "".to_owned()),
+        };
+
+        self.timer.set_text(&time_left);
+        self.level.set_text(&level_name);
+        self.moves
+            .set_text(&format!("Moves: {}  Pushes: {}", score.steps, score.pushes));
+        self.hints.set_text(&format!("Hints: {}", hints.used));
+        self.warning
+            .set_text(if game.settings.show_deadlocks && deadlocks.is_stuck() {
+                "Stuck! U to undo, R to restart"
+            } else {
+                ""
+            });
+    }
+
+    pub fn draw(&mut self, window: &mut Window, game: &mut Game) -> Result<()> {
+        let header_height = game.settings.header_height;
+        let font = &game.game_asset.mali_font;
+
+        self.timer.draw(window, header_height, font)?;
+        self.level.draw(window, header_height, font)?;
+        self.moves.draw(window, header_height, font)?;
+        self.hints.draw(window, header_height, font)?;
+        self.warning.draw(window, header_height, font)
+    }
+}
+
+/// A title in the middle of the window with lines of text below it, for the
+/// screens that are mostly text.
+pub struct TextPanel {
+    title: CachedText,
+    lines: Vec<CachedText>,
+}
+
+impl TextPanel {
+    pub fn new(title: &str, lines: &[&str]) -> Self {
+        let mut text_panel = TextPanel {
+            title: CachedText::new(title, FontStyle::new(72.0, Color::WHITE)),
+            lines: vec![],
+        };
+        text_panel.set_lines(
+            &lines
+                .iter()
+                .map(|line| line.to_string())
+                .collect::<Vec<_>>(),
+        );
+        text_panel
+    }
+
+    /// Changes the lines below the title, keeping the ones that are the same.
+    pub fn set_lines(&mut self, lines: &[String]) {
+        self.lines.truncate(lines.len());
+
+        lines.iter().enumerate().for_each(|(index, line)| {
+            if index < self.lines.len() {
+                self.lines[index].set_text(line);
+            } else {
+                self.lines
+                    .push(CachedText::new(line, FontStyle::new(32.0, Color::WHITE)));
+            }
+        });
+    }
+
+    pub fn draw(&mut self, window: &mut Window, font: &Font) -> Result<()> {
+        let center_x = f32::from(WINDOW_WIDTH) / 2.;
+        let center_y = f32::from(WINDOW_HEIGHT) / 2.;
+
+        self.title
+            .draw(window, font, |_area| Vector::new(center_x, center_y))?;
+
+        self.lines
+            .iter_mut()
+            .enumerate()
+            .map(|(index, line)| {
+                let y = center_y + 70. + index as f32 * 40.;
+                line.draw(window, font, |_area| Vector::new(center_x, y))
+            })
+            .collect()
+    }
+}
@@ -9,7 +9,9 @@ extern crate nalgebra;
 extern crate ncollide2d;
 extern crate quicksilver;
 extern crate serde;
+#[macro_use]
 extern crate serde_json;
+extern crate serde_path_to_error;
 extern crate specs;
 extern crate time;
 
@@ -19,26 +21,69 @@ extern crate log;
 /*extern crate console_error_panic_hook;*/
 //pub use console_error_panic_hook::set_once as set_panic_hook;
 
+mod animation;
+mod assets;
+pub mod atlas;
 mod character;
+mod collectible;
 mod collision;
+mod deadlock;
+mod editor;
+mod enemy;
+mod floor;
+mod gameplay;
+mod gravity;
+mod hint;
+mod hud;
+mod loading;
 pub mod map;
+mod matching;
+mod menu;
 mod physics;
+mod push;
+mod save;
+mod scene;
+mod scenes;
+mod schema;
+mod score;
+pub mod solver;
 mod stages;
+mod teleport;
+mod trigger;
 mod utils;
-
-use character::{Character, CharacterPosition};
-use collision::{Collision, CollisionSystem};
-use futures::future;
+mod watcher;
+
+use animation::AnimationSystem;
+use assets::GameAsset;
+use character::Character;
+use collectible::CollectibleSystem;
+use collision::{BlockHandle, Collision, CollisionSystem};
+use deadlock::{DeadlockSystem, Deadlocks};
+use editor::Editor;
+use enemy::EnemySystem;
+use floor::{Floor, FloorSystem};
+use gravity::GravitySystem;
+use hint::Hints;
+use loading::LoadingScene;
+use matching::MatchSystem;
 use physics::{DeltaTime, PhysicsSystem, Position, Velocity};
+use push::{PushHistory, PushSystem, Strain};
+use save::{LevelId, SaveGame, SaveStorage};
+use scene::SceneStack;
+use scenes::TitleScene;
+use score::{Score, ScoreSystem};
+use teleport::{TeleportSystem, Teleporters};
+use trigger::TriggerSystem;
+use watcher::StagesWatcher;
 //use log::log;
-use map::{Block, BlockSystem, Map, Stage, StageCreator};
+use map::{BlockSystem, CurrentLevel, Level, Stage, StageCreator};
+use menu::MenuEntry;
 use nalgebra::Vector2;
 use quicksilver::{
-    geom::{Rectangle, Shape, Vector},
-    graphics::{Animation, Background::Img, Color, Font, FontStyle, Image},
-    input::{ButtonState, Key},
-    lifecycle::{run, Asset, Settings as QuickSilverSettings, State, Window},
-    load_file, Future, Result,
+    geom::Vector,
+    graphics::Color,
+    lifecycle::{run, Settings as QuickSilverSettings, State, Window},
+    Result,
 };
 use specs::{Builder, Component, Dispatcher, DispatcherBuilder, Join, VecStorage, World};
 use std::time::Duration;
@@ -46,13 +91,7 @@ use std::time::Duration;
 const WINDOW_WIDTH: u16 = 600;
 const WINDOW_HEIGHT: u16 = 600;
 const DESIRED_FPS: u32 = 60;
-
-#[derive(Debug, Deserialize, Serialize)]
-pub enum GameState {
-    Active,
-    Paused,
-    Over,
-}
+const HEADER_HEIGHT: f32 = 100.;
 
 #[derive(Debug, Deserialize, Serialize)]
 pub enum DrawState {
@@ -60,17 +99,10 @@ pub enum DrawState {
     Undrawed,
 }
 
-impl Default for GameState {
-    fn default() -> Self {
-        GameState::Active
-    }
-}
-
 #[derive(Debug, Deserialize, Serialize)]
 pub struct ScreenState {
     pub current_stage: u16,
     pub current_level: u16,
-    pub game_state: GameState,
     pub draw_state: DrawState,
 }
 
@@ -79,251 +111,359 @@ impl Default for ScreenState {
         ScreenState {
             current_stage: 1,
             current_level: 1,
-            game_state: GameState::Active,
             draw_state: DrawState::Undrawed,
         }
     }
 }
 
+impl ScreenState {
+    pub fn level_id(&self) -> LevelId {
+        LevelId::new(self.current_stage, self.current_level)
+    }
+}
+
 impl Component for ScreenState {
     type Storage = VecStorage<Self>;
 }
 
-pub struct Screen<'a> {
+/// Everything the scenes share: the world, the assets and the settings.
+pub struct Game<'a> {
     world: World,
     time_elapsed: Duration,
     settings: Settings,
     game_asset: GameAsset,
     dispatcher: Dispatcher<'a, 'a>,
+    storage: Box<dyn SaveStorage>,
+    stages_watcher: StagesWatcher,
 }
 
-impl<'a> Screen<'a> {
-    fn draw_character(
-        window: &mut Window,
-        position: &Position,
-        character_animation: &Animation,
-    ) -> Result<()> {
-        let current_frame = character_animation.current_frame();
-        window.draw(
-            &current_frame.area().with_center(position.0),
-            Img(&current_frame),
+pub enum Screen<'a> {
+    Loading(LoadingScene),
+    Running(Game<'a>, SceneStack),
+}
+
+impl<'a> Game<'a> {
+    fn new(settings: Settings, game_asset: GameAsset, stages: Vec<Stage>) -> Self {
+        let mut world = World::new();
+
+        let collisions = PhysicsSystem::init_collision_world();
+
+        world.add_resource(DeltaTime(1. / DESIRED_FPS as f32));
+        world.add_resource(collisions);
+        world.add_resource(CurrentLevel::default());
+        world.add_resource(Hints::default());
+        world.add_resource(BlockSize(settings.block_size));
+        world.register::<BlockHandle>();
+
+        let mut dispatcher: Dispatcher = DispatcherBuilder::new()
+            .with(StageCreator, "stage_creator", &[])
+            .with(PushSystem, "push_system", &[])
+            .with(GravitySystem, "gravity_system", &["push_system"])
+            .with(
+                CollisionSystem,
+                "collision_system",
+                &["push_system", "gravity_system"],
+            )
+            .with(PhysicsSystem, "physics_system", &["collision_system"])
+            .with(CollectibleSystem, "collectible_system", &["physics_system"])
+            .with(
+                TeleportSystem,
+                "teleport_system",
+                &["push_system", "physics_system"],
+            )
+            .with(
+                FloorSystem,
+                "floor_system",
+                &["push_system", "physics_system", "teleport_system"],
+            )
+            .with(
+                MatchSystem,
+                "match_system",
+                &["push_system", "teleport_system", "floor_system"],
+            )
+            .with(
+                TriggerSystem,
+                "trigger_system",
+                &["push_system", "physics_system", "floor_system"],
+            )
+            .with(
+                EnemySystem,
+                "enemy_system",
+                &["push_system", "physics_system", "trigger_system"],
+            )
+            .with(
+                DeadlockSystem,
+                "deadlock_system",
+                &["push_system", "floor_system", "trigger_system"],
+            )
+            .with(
+                ScoreSystem,
+                "score_system",
+                &["push_system", "physics_system"],
+            )
+            .with(
+                AnimationSystem,
+                "animation_system",
+                &["push_system", "physics_system"],
+            )
+            .with(BlockSystem, "block_system", &[])
+            .build();
+
+        dispatcher.setup(&mut world.res);
+
+        let storage = save::default_storage();
+        let save_game = save::load(&*storage);
+
+        {
+            let mut screen_state = world.write_resource::<ScreenState>();
+            screen_state.current_stage = save_game.screen_state.current_stage;
+            screen_state.current_level = save_game.screen_state.current_level;
+        }
+
+        world.add_resource(save_game);
+
+        //map::create_base_map_entities(&mut world, &settings)?;
+
+        dispatcher.dispatch(&world.res);
+
+        world.maintain();
+
+        PhysicsSystem::setup_handles(
+            &settings,
+            &world.entities(),
+            &mut world.write_resource::<Collision>(),
+            &world.read_storage::<Velocity>(),
+            &world.read_storage::<Position>(),
+            &world.read_storage::<Character>(),
         );
-        Ok(())
-    }
 
-    fn draw_time_left(
-        window: &mut Window,
-        time_elapsed: &Duration,
-        map: &Map,
-        asset_font: &mut Asset<Font>,
-    ) -> Result<()> {
-        let font_style = FontStyle::new(72.0, Color::WHITE);
-        let map_time = map.time / 1000;
-        let time_elapsed_as_secs = time_elapsed.as_secs();
-
-        if time_elapsed_as_secs <= map_time {
-            asset_font.execute(|font| {
-                let _ = font
-                    .render(&format!("{}", map_time - time_elapsed_as_secs), &font_style)
-                    .map(|text| {
-                        window.draw(&text.area().with_center((70, 50)), Img(&text));
-                    });
-                Ok(())
-            })
-        } else {
-            Ok(())
+        stages.into_iter().for_each(|stage| {
+            world.create_entity().with(stage).build();
+        });
+
+        let stages_watcher = StagesWatcher::new(&settings.stages_save_path);
+
+        Game {
+            world,
+            time_elapsed: Duration::new(0, 0),
+            settings,
+            game_asset,
+            dispatcher,
+            storage,
+            stages_watcher,
         }
     }
 
-    fn draw_blocks(
-        window: &mut Window,
-        map: &Map,
-        block_asset: &mut Asset<Image>,
-    ) -> Vec<Result<()>> {
-        map.blocks_with_position
-            .iter()
-            .map(|block_with_position| {
-                let position = block_with_position.position.0;
-
-                block_asset.execute(|image| {
-                    window.draw(&image.area().with_center(position), Img(&image));
-                    Ok(())
-                })
-            })
-            .collect::<Vec<Result<_>>>()
-    }
+    /// Replaces the stages when the stages file has changed and rebuilds the
+    /// current level from them. Keeps the previous stages if the new file can't
+    /// be used.
+    fn reload_stages(&mut self) {
+        let stages_bytes = match self.stages_watcher.poll() {
+            Some(stages_bytes) => stages_bytes,
+            None => return,
+        };
 
-    fn tick_character_animation(
-        current_frame_area: &Rectangle,
-        start_position: Option<&CharacterPosition>,
-        moving_position: Option<&CharacterPosition>,
-        character_animation: &mut Animation,
-    ) {
-        let tick_animation =
-            |x: &f32, rectangle: &Rectangle, character_animation: &mut Animation| {
-                if x == &rectangle.pos.x {
-                    debug!("Ticking character animation: {} {:?}", x, rectangle);
-                    character_animation.tick();
-                }
-            };
-        match current_frame_area {
-            Rectangle {
-                pos: Vector { x, .. },
-                ..
-            } => {
-                if let Some(start_position) = start_position {
-                    if let CharacterPosition::Start(rectangle) = start_position {
-                        tick_animation(x, rectangle, character_animation);
-                    }
-                }
-                if let Some(moving_position) = moving_position {
-                    if let CharacterPosition::Moving(rectangle) = moving_position {
-                        tick_animation(x, rectangle, character_animation);
-                    }
+        match map::load_stages(&stages_bytes) {
+            Ok(stages) => {
+                info!("Reloading stages from {}", self.stages_watcher.path());
+                {
+                    let entities = self.world.entities();
+                    let stage_storage = self.world.read_storage::<Stage>();
+                    (&entities, &stage_storage)
+                        .join()
+                        .for_each(|(entity, _stage)| {
+                            let _ = entities.delete(entity);
+                        });
                 }
+                self.world.maintain();
+
+                stages.into_iter().for_each(|stage| {
+                    self.world.create_entity().with(stage).build();
+                });
+                self.restart_level();
             }
+            Err(err) => error!(
+                "Keeping the previous stages, {} couldn't be used: {}",
+                self.stages_watcher.path(),
+                err
+            ),
         }
     }
 
-    fn handle_keys_to_change_velocity(
-        settings: &Settings,
-        velocity: &mut Velocity,
-        window: &mut Window,
-        character_animation: &mut Animation,
-        animation_positions: &Vec<CharacterPosition>,
-    ) {
-        let velocity_change = settings.velocity_change;
-        let mut animation_positions_iter = animation_positions.into_iter();
-
-        let start_position = animation_positions_iter.find(|pos| match pos {
-            CharacterPosition::Start(_) => true,
-            _ => false,
-        });
-        let moving_position = animation_positions_iter.find(|pos| match pos {
-            CharacterPosition::Moving(_) => true,
-            _ => false,
-        });
+    /// Loads the current level once the stages are available, placing the
+    /// character at the map's start position.
+    fn load_level(&mut self) -> Result<()> {
+        if let DrawState::Drawed = self.world.read_resource::<ScreenState>().draw_state {
+            return Ok(());
+        }
 
-        let current_frame_area = character_animation.current_frame().area();
-
-        let mut key_match =
-            |key: Key,
-             on_press: Box<Fn(&mut Velocity) -> ()>,
-             on_release: Box<Fn(&mut Velocity) -> ()>| match window.keyboard()[key] {
-                ButtonState::Pressed | ButtonState::Held => {
-                    debug!("Key: {:?} pressed or held", key);
-
-                    on_press(velocity);
-                    Self::tick_character_animation(
-                        &current_frame_area,
-                        start_position,
-                        None,
-                        character_animation,
-                    );
-                }
-                ButtonState::Released => {
-                    debug!("Key: {:?} released", key);
-
-                    on_release(velocity);
-                    Self::tick_character_animation(
-                        &current_frame_area,
-                        None,
-                        moving_position,
-                        character_animation,
-                    );
-                }
-                ButtonState::NotPressed => {}
+        let world = &mut self.world;
+        let settings = &self.settings;
+        let time_elapsed = &mut self.time_elapsed;
+
+        let has_stages = world.read_storage::<Stage>().join().next().is_some();
+        if !has_stages {
+            return Ok(());
+        }
+
+        let current_map = {
+            let stages = map::stages_from_world(world);
+            let screen_state = world.read_resource::<ScreenState>();
+            map::find_current_map(stages, &screen_state)
+        };
+
+        if let Some(map) = current_map {
+            info!("Loading level {}", map.level);
+            let stage = world.read_resource::<ScreenState>().current_stage;
+            let snapshot = world
+                .write_resource::<SaveGame>()
+                .snapshot
+                .take()
+                .filter(|snapshot| snapshot.id == LevelId::new(stage, map.level));
+
+            let playing_map = snapshot
+                .as_ref()
+                .map_or_else(|| map.to_owned(), |snapshot| snapshot.apply(&map));
+            map::create_map_entities(world, settings, &playing_map);
+            *world.write_resource::<Floor>() = Floor::from_map(&playing_map);
+            *world.write_resource::<Teleporters>() = Teleporters::from_map(&playing_map);
+            trigger::create_doors(world, settings, &playing_map);
+            enemy::create_enemies(world, &playing_map);
+            collectible::create_collectibles(world, &playing_map);
+            map::place_character(world, settings, &playing_map);
+            let bonus_time = snapshot.as_ref().map_or(0, |snapshot| snapshot.bonus_time);
+            *time_elapsed = snapshot.map_or(Duration::new(0, 0), |snapshot| {
+                info!("Resuming level {} from the save game", map.level);
+                Duration::from_millis(snapshot.time_elapsed)
+            });
+
+            let mut level = Level::new(stage, map);
+            level.map.time += bonus_time;
+            *world.write_resource::<CurrentLevel>() = CurrentLevel(Some(level));
+            *world.write_resource::<Hints>() = Hints::default();
+            *world.write_resource::<PushHistory>() = PushHistory::default();
+            *world.write_resource::<Strain>() = Strain::default();
+            *world.write_resource::<Deadlocks>() = Deadlocks::default();
+            *world.write_resource::<Score>() = Score {
+                bonus_time,
+                ..Score::default()
             };
+        }
 
-        key_match(
-            Key::Up,
-            Box::new(move |velocity| velocity.0.y = -velocity_change),
-            Box::new(|velocity| velocity.0.y = velocity.0.y.max(0.)),
-        );
-        key_match(
-            Key::Down,
-            Box::new(move |velocity| velocity.0.y = velocity_change),
-            Box::new(|velocity| velocity.0.y = velocity.0.y.min(0.)),
-        );
-        key_match(
-            Key::Left,
-            Box::new(move |velocity| velocity.0.x = -velocity_change),
-            Box::new(|velocity| velocity.0.x = velocity.0.x.max(0.)),
-        );
-        key_match(
-            Key::Right,
-            Box::new(move |velocity| velocity.0.x = velocity_change),
-            Box::new(|velocity| velocity.0.x = velocity.0.x.min(0.)),
-        );
-    }
+        world.write_resource::<ScreenState>().draw_state = DrawState::Drawed;
 
-    fn load_fonts(settings: &Settings) -> Asset<Font> {
-        info!("Loading fonts from path: {}", settings.mali_font_path);
-        Asset::new(Font::load(settings.mali_font_path.to_owned()))
+        Ok(())
     }
 
-    fn load_character_asset(
-        animation_positions: Vec<Rectangle>,
-        settings: &Settings,
-    ) -> Asset<Animation> {
-        let frame_delay = 1;
+    /// Stores the progress, including where everything is in the current level.
+    fn save_progress(&mut self) {
+        let snapshot = save::snapshot(&self.world, &self.time_elapsed);
+        let screen_state = self.world.read_resource::<ScreenState>();
+        let mut save_game = self.world.write_resource::<SaveGame>();
 
-        info!(
-            "Loading character asset from path: {} with frame delay: {}",
-            settings.character_sprites_path, frame_delay
-        );
+        save_game.screen_state.current_stage = screen_state.current_stage;
+        save_game.screen_state.current_level = screen_state.current_level;
+        if snapshot.is_some() {
+            save_game.snapshot = snapshot;
+        }
 
-        let character_image =
-            Image::load(settings.character_sprites_path.to_owned()).map(move |character_image| {
-                Animation::from_spritesheet(
-                    character_image.to_owned(),
-                    animation_positions,
-                    frame_delay,
-                )
-            });
+        if let Err(err) = save::store(&*self.storage, &save_game) {
+            error!("Failed to save the game with error: {}", err);
+        }
+    }
 
-        Asset::new(character_image)
+    /// Records the completed level in the save game and continues from the next
+    /// level when the game is started again.
+    fn complete_level(&mut self) {
+        let stages = map::stages_from_world(&self.world);
+        let screen_state = self.world.read_resource::<ScreenState>();
+        let mut save_game = self.world.write_resource::<SaveGame>();
+
+        let current_level = self.world.read_resource::<CurrentLevel>();
+        let mut score = self.world.write_resource::<Score>();
+
+        let id = LevelId::new(screen_state.current_stage, screen_state.current_level);
+        let time =
+            self.time_elapsed.as_secs() * 1000 + u64::from(self.time_elapsed.subsec_millis());
+        let next = id.next(&stages);
+
+        score.time_left = current_level
+            .0
+            .as_ref()
+            .map(|level| level.map.time.saturating_sub(time));
+
+        save_game.complete_level(id, time, &score, next);
+        if let Some(next) = next {
+            save_game.screen_state.current_stage = next.stage;
+            save_game.screen_state.current_level = next.level;
+        }
+
+        if let Err(err) = save::store(&*self.storage, &save_game) {
+            error!("Failed to save the game with error: {}", err);
+        }
     }
 
-    fn load_block_asset(settings: &Settings) -> Asset<Image> {
-        info!(
-            "Loading block assets from path: {}",
-            settings.block_asset_path
-        );
-        Asset::new(Image::load(settings.block_asset_path.to_owned()))
+    /// The stage and level that is being played, or would be played next.
+    fn current_level_id(&self) -> LevelId {
+        self.world.read_resource::<ScreenState>().level_id()
     }
 
-    fn load_stages(settings: &Settings) -> Asset<Vec<Stage>> {
-        info!("Loading stages from path: {}", settings.stages_json_path);
+    fn menu_entries(&self) -> Vec<MenuEntry> {
+        MenuEntry::from_stages(
+            &map::stages_from_world(&self.world),
+            &self.world.read_resource::<SaveGame>(),
+        )
+    }
 
-        let stages_file =
-            load_file(settings.stages_json_path.to_owned()).and_then(move |stages_bytes| {
-                let stages = map::parse_json(&stages_bytes);
-                future::result(stages.map_err(|err| {
-                    error!("Failed to load json with error: {}", err);
-                    quicksilver::Error::ContextError("Couldn't parse json.".to_owned())
-                }))
-            });
+    /// Loads the level the next time the gameplay scene is updated.
+    fn start_level(&mut self, id: LevelId) {
+        let mut screen_state = self.world.write_resource::<ScreenState>();
+        screen_state.current_stage = id.stage;
+        screen_state.current_level = id.level;
+        screen_state.draw_state = DrawState::Undrawed;
+    }
 
-        Asset::new(stages_file)
+    fn restart_level(&mut self) {
+        self.world.write_resource::<ScreenState>().draw_state = DrawState::Undrawed;
     }
-}
 
-struct GameAsset {
-    mali_font: Asset<Font>,
-    character_asset: Asset<Animation>,
-    block_asset: Asset<Image>,
-    stages: Asset<Vec<Stage>>,
+    /// Writes the edited map back into its stage if it's valid, returning the
+    /// stage and level of the map.
+    fn store_editor_map(&mut self, editor: &Editor) -> Option<LevelId> {
+        if let Err(err) = editor.map.validate(editor.stage, &Character::default()) {
+            error!("Can't use the edited map: {}", err);
+            return None;
+        }
+
+        let mut stages = self.world.write_storage::<Stage>();
+        let stage = (&mut stages)
+            .join()
+            .find(|stage| stage.stage == editor.stage)?;
+
+        match stage
+            .maps
+            .iter_mut()
+            .find(|map| map.level == editor.map.level)
+        {
+            Some(map) => *map = editor.map.to_owned(),
+            None => stage.maps.push(editor.map.to_owned()),
+        }
+
+        Some(LevelId::new(editor.stage, editor.map.level))
+    }
 }
 
 #[derive(Debug)]
 pub struct Settings {
-    animation_positions: Vec<CharacterPosition>,
     mali_font_path: String,
     character_sprites_path: String,
-    block_asset_path: String,
+    atlas_path: String,
+    block_sprite: String,
+    goal_sprite: String,
     stages_json_path: String,
+    stages_save_path: String,
     header_height: f32,
+    show_deadlocks: bool,
     block_size: Vector2<f32>,
     character_size: Vector2<f32>,
     velocity_change: f32,
@@ -334,20 +474,16 @@ pub struct BlockSize(pub Vector2<f32>);
 
 impl State for Screen<'static> {
     fn new() -> Result<Self> {
-        let animation_start_position = Rectangle::new(Vector::new(0, 12), Vector::new(29, 21));
-        let animation_moving_position = Rectangle::new(Vector::new(32, 12), Vector::new(28, 21));
-        let animation_positions = vec![animation_start_position, animation_moving_position];
-
         let settings = Settings {
-            animation_positions: vec![
-                CharacterPosition::Start(animation_start_position),
-                CharacterPosition::Moving(animation_moving_position),
-            ],
             mali_font_path: "mali/Mali-Regular.ttf".to_owned(),
-            character_sprites_path: "character_sprite_0_white.png".to_owned(),
-            block_asset_path: "50x50.png".to_owned(),
+            character_sprites_path: "character_animations.json".to_owned(),
+            atlas_path: "atlas.json".to_owned(),
+            block_sprite: "50x50.png".to_owned(),
+            goal_sprite: "25x25.png".to_owned(),
             stages_json_path: "stages.json".to_owned(),
-            header_height: 100.,
+            stages_save_path: "static/stages.json".to_owned(),
+            header_height: HEADER_HEIGHT,
+            show_deadlocks: true,
             block_size: Vector2::new(25., 25.),
             character_size: Vector2::new(25., 25.),
             velocity_change: 55.0,
@@ -355,127 +491,25 @@ impl State for Screen<'static> {
 
         debug!("Starting up with settings: {:?}", &settings);
 
-        let mut world = World::new();
-
-        let collisions = PhysicsSystem::init_collision_world();
-
-        world.add_resource(DeltaTime(1. / 60.));
-        world.add_resource(collisions);
-
-        let mut dispatcher: Dispatcher = DispatcherBuilder::new()
-            .with(StageCreator, "stage_creator", &[])
-            .with(CollisionSystem, "collision_system", &[])
-            .with(PhysicsSystem, "physics_system", &["collision_system"])
-            .with(BlockSystem, "block_system", &[])
-            .build();
-
-        dispatcher.setup(&mut world.res);
-
-        world
-            .create_entity()
-            .with(Velocity(Vector2::new(0., 0.)))
-            .with(Position(Vector2::new(130., 330.)))
-            .with(Character::default())
-            .build();
-
-        world
-            .create_entity()
-            .with(Position(Vector2::new(150., 150.)))
-            .with(Block::default())
-            .build();
-
-        world
-            .create_entity()
-            .with(Position(Vector2::new(150., 200.)))
-            .with(Block::default())
-            .build();
-
-        //map::create_base_map_entities(&mut world, &settings)?;
-
-        dispatcher.dispatch(&world.res);
-
-        world.maintain();
-
-        PhysicsSystem::setup_handles(
-            &settings,
-            &world.entities(),
-            &mut world.write_resource::<Collision>(),
-            &world.read_storage::<Velocity>(),
-            &world.read_storage::<Position>(),
-            &world.read_storage::<Character>(),
-        );
-
-        let mali_font = Screen::load_fonts(&settings);
-        let block_asset = Screen::load_block_asset(&settings);
-        let character_asset = Screen::load_character_asset(animation_positions, &settings);
-        let stages = Screen::load_stages(&settings);
-
-        let game_asset = GameAsset {
-            mali_font,
-            block_asset,
-            character_asset,
-            stages,
-        };
-
-        let screen = Screen {
-            world,
-            time_elapsed: Duration::new(0, 0),
-            settings,
-            game_asset,
-            dispatcher,
-        };
-
-        Ok(screen)
+        Ok(Screen::Loading(LoadingScene::new(settings)))
     }
 
     fn update(&mut self, window: &mut Window) -> Result<()> {
-        let seconds = 1.0 / (DESIRED_FPS as f32);
-        //println!("Seconds {:?}", seconds);
-
-        //println!("{:?}", &format!("Fps: {}", window.average_fps()));
-        self.time_elapsed += Duration::from_millis(10);
-        self.world.maintain();
-        self.dispatcher.dispatch(&self.world.res);
-
-        let settings = &self.settings;
-        let mut screen_state = self.world.write_resource::<ScreenState>();
-        let characters = self.world.read_storage::<Character>();
-        let stages = self.world.read_storage::<Stage>();
-        let mut velocity_storage = self.world.write_storage::<Velocity>();
-        let entities = self.world.entities();
-
-        let character_asset = &mut self.game_asset.character_asset;
-        let animation_positions = &self.settings.animation_positions;
-        let time_elapsed = self.time_elapsed;
-
-        entities.join().for_each(|entity| {
-            if let Some(stage) = stages.get(entity) {
-                stage
-                    .maps
-                    .iter()
-                    .find(|map| map.level == screen_state.current_level)
-                    .map(|map| {
-                        if time_elapsed.as_secs() >= map.time / 1000 {
-                            screen_state.game_state = GameState::Over;
-                        }
-                    });
+        let loaded = match self {
+            Screen::Loading(loading_scene) => loading_scene.update(),
+            Screen::Running(game, scenes) => {
+                game.reload_stages();
+                return scenes.update(game, window);
             }
+        };
 
-            if let Some(_character) = characters.get(entity) {
-                if let Some(velocity) = velocity_storage.get_mut(entity) {
-                    let _ = character_asset.execute(|character_animation| {
-                        Screen::handle_keys_to_change_velocity(
-                            &settings,
-                            velocity,
-                            window,
-                            character_animation,
-                            animation_positions,
-                        );
-                        Ok(())
-                    });
-                }
-            }
-        });
+        if let Some((settings, game_asset, stages)) = loaded {
+            info!("Loaded all assets");
+            *self = Screen::Running(
+                Game::new(settings, game_asset, stages),
+                SceneStack::new(Box::new(TitleScene::new())),
+            );
+        }
 
         Ok(())
     }
@@ -484,116 +518,10 @@ impl State for Screen<'static> {
         window.clear(Color::BLACK)?;
         //log(&format!("Fps: {}", window.average_fps()));
 
-        let world = &mut self.world;
-        let entities = world.entities();
-        let characters = world.read_storage::<Character>();
-        let screen_state = world.write_resource::<ScreenState>();
-        let positions = world.read_storage::<Position>();
-        let stages = world.write_storage::<Stage>();
-        let blocks = world.read_storage::<Block>();
-
-        let font_style = FontStyle::new(72.0, Color::WHITE);
-
-        let time_elapsed = self.time_elapsed;
-        let mali_font = &mut self.game_asset.mali_font;
-        let block_asset = &mut self.game_asset.block_asset;
-        let character_asset = &mut self.game_asset.character_asset;
-        //let stages_asset = &mut self.game_asset.stages;
-
-        if let DrawState::Undrawed = screen_state.draw_state {
-
-            /*let _ = stages_asset.execute(|fetched_stages| {*/
-            //fetched_stages.iter().for_each(|stage| {
-            //let entity = entities.create();
-            //let _ = stages.insert(entity, stage.to_owned());
-            //});
-            //screen_state.draw_state = DrawState::Drawed;
-
-            //Ok(())
-            /*});*/
+        match self {
+            Screen::Loading(loading_scene) => loading_scene.draw(window),
+            Screen::Running(game, scenes) => scenes.draw(game, window),
         }
-
-        let mut active_rendering = |entity: specs::Entity,
-                                    window: &mut Window,
-                                    block_asset: &mut Asset<Image>,
-                                    mali_font: &mut Asset<Font>|
-         -> Result<()> {
-            if let Some(position) = positions.get(entity) {
-                if let Some(_character) = characters.get(entity) {
-                    block_asset.execute(|image| {
-                        //println!("POS RENDERING {:?}", position);
-                        window.draw(&image.area().with_center(position.0), Img(&image));
-                        Ok(())
-                    });
-                    /*                    character_asset.execute(|character_image| {*/
-                    //Screen::draw_character(window, position, character_image)?;
-                    //Ok(())
-                    /*})?;*/
-                }
-            }
-
-            positions.get(entity).and_then(|position| {
-                blocks.get(entity).map(|_block| {
-                    block_asset.execute(|image| {
-                        window.draw(&image.area().with_center(position.0), Img(&image));
-                        Ok(())
-                    })
-                })
-            });
-
-            let current_map = stages.get(entity).and_then(|stage| {
-                stage
-                    .maps
-                    .iter()
-                    .find(|map| map.level == screen_state.current_level)
-            });
-
-            match current_map {
-                Some(map) => {
-                    Screen::draw_time_left(window, &time_elapsed, map, mali_font)?;
-
-                    Screen::draw_blocks(window, map, block_asset);
-                }
-                None => {}
-            };
-
-            Ok(())
-        };
-
-        entities
-            .join()
-            .map(|entity| match screen_state.game_state {
-                GameState::Active => active_rendering(entity, window, block_asset, mali_font),
-                GameState::Active => Ok(()),
-                GameState::Over => {
-                    active_rendering(entity, window, block_asset, mali_font)?;
-                    mali_font.execute(|font| {
-                        let _ = font.render("Game over", &font_style).map(|text| {
-                            window.draw(
-                                &text
-                                    .area()
-                                    .with_center((WINDOW_WIDTH / 2, WINDOW_HEIGHT / 2)),
-                                Img(&text),
-                            );
-                        });
-
-                        Ok(())
-                    })
-                }
-                GameState::Paused => mali_font.execute(|font| {
-                    let _ = font.render("Paused", &font_style).map(|text| {
-                        window.draw(
-                            &text
-                                .area()
-                                .with_center((WINDOW_WIDTH / 2, WINDOW_HEIGHT / 2)),
-                            Img(&text),
-                        );
-                    });
-
-                    Ok(())
-                }),
-            })
-            .collect()
     }
 }
 
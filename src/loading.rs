@@ -0,0 +1,112 @@
+use assets::{AssetLoader, AssetManifest, GameAsset, LoadError};
+use hud::TextPanel;
+use map::Stage;
+use quicksilver::{
+    geom::{Rectangle, Shape},
+    graphics::{Background::Col, Color},
+    lifecycle::Window,
+    Result,
+};
+use Settings;
+use WINDOW_HEIGHT;
+use WINDOW_WIDTH;
+
+const PROGRESS_BAR_WIDTH: f32 = 400.;
+const PROGRESS_BAR_HEIGHT: f32 = 20.;
+/// How many characters fit on a line of the error screen.
+const LINE_LENGTH: usize = 36;
+
+/// Splits the text into lines of at most `LINE_LENGTH` characters, between words
+/// where it can.
+fn wrap(text: &str) -> Vec<String> {
+    text.split_whitespace().fold(vec![], |mut lines, word| {
+        let is_fitting = lines.last().map_or(false, |line: &String| {
+            line.len() + 1 + word.len() <= LINE_LENGTH
+        });
+
+        if is_fitting {
+            if let Some(line) = lines.last_mut() {
+                line.push(' ');
+                line.push_str(word);
+            }
+        } else {
+            lines.push(word.to_owned());
+        }
+        lines
+    })
+}
+
+/// Shows the progress of loading the assets in the manifest, or which file
+/// failed to load. It runs before the game exists, so it isn't on the scene
+/// stack.
+pub struct LoadingScene {
+    settings: Option<Settings>,
+    loader: AssetLoader,
+    error: Option<LoadError>,
+    text_panel: TextPanel,
+}
+
+impl LoadingScene {
+    pub fn new(settings: Settings) -> Self {
+        let loader = AssetLoader::new(&AssetManifest::new(&settings));
+
+        LoadingScene {
+            settings: Some(settings),
+            loader,
+            error: None,
+            text_panel: TextPanel::new("Loading", &[]),
+        }
+    }
+
+    /// Returns the settings, the assets and the stages once everything has been
+    /// loaded.
+    pub fn update(&mut self) -> Option<(Settings, GameAsset, Vec<Stage>)> {
+        if self.error.is_some() {
+            return None;
+        }
+
+        match self.loader.poll() {
+            Ok(Some((game_asset, stages))) => self
+                .settings
+                .take()
+                .map(|settings| (settings, game_asset, stages)),
+            Ok(None) => None,
+            Err(err) => {
+                error!("{}", err);
+
+                let mut lines = vec![err.path.to_owned()];
+                lines.extend(wrap(&err.reason));
+                self.text_panel = TextPanel::new("Couldn't load", &[]);
+                self.text_panel.set_lines(&lines);
+                self.error = Some(err);
+                None
+            }
+        }
+    }
+
+    pub fn draw(&mut self, window: &mut Window) -> Result<()> {
+        let (loaded, total) = self.loader.progress();
+        let progress = loaded as f32 / total as f32;
+
+        let bar = Rectangle::new_sized((PROGRESS_BAR_WIDTH, PROGRESS_BAR_HEIGHT)).with_center((
+            f32::from(WINDOW_WIDTH) / 2.,
+            f32::from(WINDOW_HEIGHT) - 100.,
+        ));
+        let bar_color = if self.error.is_some() {
+            Color::RED
+        } else {
+            Color::WHITE
+        };
+
+        window.draw(&bar, Col(bar_color.with_alpha(0.2)));
+        window.draw(
+            &Rectangle::new(bar.pos, (bar.size.x * progress, bar.size.y)),
+            Col(bar_color),
+        );
+
+        match self.loader.font() {
+            Some(font) => self.text_panel.draw(window, font),
+            None => Ok(()),
+        }
+    }
+}
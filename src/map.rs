@@ -1,11 +1,27 @@
+use animation::CharacterAnimation;
+use character::Character;
+use collision::{BlockHandle, Collision};
+use floor::Floor;
 use nalgebra::Vector2;
-use quicksilver::graphics::Color;
-use serde_json;
+use physics::{PhysicsSystem, Velocity};
+use quicksilver::{
+    geom::{Circle, Rectangle, Shape, Vector},
+    graphics::{Background::Col, Color},
+    lifecycle::Window,
+};
+use schema::{self, StagesDocument, FORMAT_VERSION};
+use serde_json::{self, Value};
+use serde_path_to_error;
+use solver::{Board, Bounds, Cell, Direction, State, GRID_SIZE};
 use specs::{
-    prelude::Resources, Builder, Component, Entities, HashMapStorage, LazyUpdate, Read,
+    prelude::Resources, Builder, Component, Entities, HashMapStorage, Join, LazyUpdate, Read,
     ReadStorage, System, VecStorage, World, WriteStorage,
 };
-use utils::de_color;
+use std::collections::HashMap;
+use std::fmt;
+use teleport::Teleporters;
+use trigger::Triggers;
+use utils::{de_color, de_optional_color, palette_index, se_color, se_optional_color, COLORS};
 use Position;
 use ScreenState;
 use Settings;
@@ -24,6 +40,123 @@ pub fn find_current_map(stages: Vec<Stage>, state: &ScreenState) -> Option<Map>
         })
 }
 
+pub fn stages_from_world(world: &World) -> Vec<Stage> {
+    let stages = world.read_storage::<Stage>();
+    (&stages).join().cloned().collect()
+}
+
+/// Builds the solver state from the character and the blocks in the world that
+/// aren't fixed.
+pub fn current_state(world: &World) -> Option<State> {
+    state_from_storages(
+        &world.read_storage::<Character>(),
+        &world.read_storage::<Block>(),
+        &world.read_storage::<Position>(),
+        &world.read_resource::<Floor>(),
+        &world.read_resource::<Triggers>(),
+    )
+}
+
+pub fn state_from_storages(
+    characters: &ReadStorage<Character>,
+    blocks: &ReadStorage<Block>,
+    positions: &ReadStorage<Position>,
+    floor: &Floor,
+    triggers: &Triggers,
+) -> Option<State> {
+    let character = (characters, positions)
+        .join()
+        .next()
+        .map(|(_character, position)| Cell::from_position(&position.0))?;
+
+    let blocks = (blocks, positions)
+        .join()
+        .filter(|(block, _position)| block.kind != BlockKind::Fixed)
+        .map(|(block, position)| {
+            (
+                Cell::from_position(&position.0),
+                block.kind,
+                palette_index(&block.color),
+            )
+        })
+        .collect();
+
+    Some(State::new(
+        character,
+        blocks,
+        floor.holes(),
+        triggers.active_cells(),
+    ))
+}
+
+/// A level is complete when every movable block is on a goal of its color or,
+/// when matches are cleared, has been cleared.
+pub fn is_level_complete(world: &World) -> bool {
+    let current_level = world.read_resource::<CurrentLevel>();
+
+    match (&current_level.0, current_state(world)) {
+        (Some(level), Some(state)) => {
+            let board = &level.board;
+            (!board.goals.is_empty() || board.clears_matches) && board.is_solved(&state)
+        }
+        _ => false,
+    }
+}
+
+/// Replaces the blocks of the previous level with the blocks of the given map,
+/// along with their collision objects.
+pub fn create_map_entities(world: &mut World, settings: &Settings, map: &Map) {
+    info!("Creating entities for level {}", map.level);
+
+    {
+        let entities = world.entities();
+        let blocks = world.read_storage::<Block>();
+        let block_handles = world.read_storage::<BlockHandle>();
+        let mut collision = world.write_resource::<Collision>();
+
+        let handles = (&entities, &blocks)
+            .join()
+            .map(|(entity, _block)| {
+                let _ = entities.delete(entity);
+                block_handles.get(entity).map(|block_handle| block_handle.0)
+            })
+            .filter_map(|handle| handle)
+            .collect::<Vec<_>>();
+
+        if let Some(ref mut collision_world) = collision.world {
+            collision_world.remove(&handles);
+        }
+    }
+
+    world.maintain();
+
+    map.blocks_with_position
+        .iter()
+        .for_each(|block_with_position| create_block(world, settings, block_with_position));
+}
+
+/// Creates a block entity along with its collision object.
+pub fn create_block(
+    world: &mut World,
+    settings: &Settings,
+    block_with_position: &BlockAndPosition,
+) {
+    let handle = PhysicsSystem::add_block_handle(
+        settings,
+        &mut world.write_resource::<Collision>(),
+        &block_with_position.position,
+    );
+    let builder = world
+        .create_entity()
+        .with(block_with_position.block.to_owned())
+        .with(block_with_position.position.to_owned());
+
+    match handle {
+        Some(handle) => builder.with(BlockHandle(handle)).build(),
+        None => builder.build(),
+    };
+}
+
 pub fn create_base_map_entities(
     world: &mut World,
     settings: &Settings,
@@ -73,8 +206,243 @@ pub fn create_base_map_entities(
     Ok(())
 }
 
-pub fn parse_json(json_slice: &[u8]) -> Result<Vec<Stage>, serde_json::error::Error> {
-    serde_json::from_slice::<Vec<Stage>>(json_slice)
+/// Moves the character to the start position of the given map, creating the
+/// character entity if it doesn't exist yet.
+pub fn place_character(world: &mut World, settings: &Settings, map: &Map) {
+    info!("Placing character at start position: {:?}", map.start);
+    move_character(world, settings, &map.start);
+}
+
+/// Moves the character to the given position and stops it, creating the character
+/// entity if it doesn't exist yet. It isn't teleported if the position is on a
+/// teleporter.
+pub fn move_character(world: &mut World, settings: &Settings, position: &Position) {
+    let character_entity = {
+        let entities = world.entities();
+        let characters = world.read_storage::<Character>();
+        (&entities, &characters)
+            .join()
+            .map(|(entity, _character)| entity)
+            .next()
+    };
+
+    match character_entity {
+        Some(entity) => {
+            let _ = world
+                .write_storage::<Position>()
+                .insert(entity, position.to_owned());
+            let _ = world
+                .write_storage::<Velocity>()
+                .insert(entity, Velocity(Vector2::new(0., 0.)));
+            let _ = world
+                .write_storage::<CharacterAnimation>()
+                .insert(entity, CharacterAnimation::default());
+        }
+        None => {
+            world
+                .create_entity()
+                .with(Velocity(Vector2::new(0., 0.)))
+                .with(position.to_owned())
+                .with(Character::default())
+                .with(CharacterAnimation::default())
+                .build();
+        }
+    }
+
+    PhysicsSystem::place_character_handle(
+        settings,
+        &mut world.write_resource::<Collision>(),
+        position,
+    );
+    world
+        .write_resource::<Teleporters>()
+        .place_character(position);
+}
+
+fn parse_error(path: String, err: &serde_json::Error) -> MapError {
+    let location = format!(" at line {} column {}", err.line(), err.column());
+
+    MapError::Parse {
+        line: err.line(),
+        column: err.column(),
+        path,
+        reason: err
+            .to_string()
+            .trim_end_matches(location.as_str())
+            .to_owned(),
+    }
+}
+
+/// Parses the stages, upgrading files written in an older format, and points
+/// out where in the json it went wrong when it fails.
+pub fn parse_json(json_slice: &[u8]) -> Result<Vec<Stage>, MapError> {
+    let document: Value =
+        serde_json::from_slice(json_slice).map_err(|err| parse_error(".".to_owned(), &err))?;
+    let version = schema::version(&document)?;
+
+    let document: Result<StagesDocument, _> = if version == FORMAT_VERSION {
+        // Parsed again from the bytes so the errors know the line and column.
+        let mut deserializer = serde_json::Deserializer::from_slice(json_slice);
+        serde_path_to_error::deserialize(&mut deserializer)
+    } else {
+        serde_path_to_error::deserialize(schema::migrate(document, version)?)
+    };
+
+    document
+        .map(|document| document.stages)
+        .map_err(|err| parse_error(err.path().to_string(), err.inner()))
+}
+
+/// Parses the stages and checks that every map in them can be played.
+pub fn load_stages(json_slice: &[u8]) -> Result<Vec<Stage>, MapError> {
+    parse_json(json_slice).and_then(|stages| validate_stages(&stages).map(|_| stages))
+}
+
+pub fn validate_stages(stages: &[Stage]) -> Result<(), MapError> {
+    let character = Character::default();
+
+    stages.iter().fold(Ok(()), |result, stage| {
+        result.and_then(|_| {
+            stage.maps.iter().fold(Ok(()), |result, map| {
+                result.and_then(|_| map.validate(stage.stage, &character))
+            })
+        })
+    })
+}
+
+#[derive(Debug)]
+pub enum MapError {
+    /// The json doesn't describe stages. The path leads from the document
+    /// to the value that is wrong, like `stages[0].maps[1].blocks[7].color`.
+    Parse {
+        line: usize,
+        column: usize,
+        path: String,
+        reason: String,
+    },
+    MissingVersion,
+    /// The file was written by a newer version of the game.
+    UnsupportedVersion(u64),
+    StartOverlapsBlock {
+        stage: u16,
+        level: u16,
+        block_index: usize,
+    },
+    StartInHole {
+        stage: u16,
+        level: u16,
+    },
+    /// There are more goals of a color than movable blocks of it.
+    GoalColorMismatch {
+        stage: u16,
+        level: u16,
+        color: &'static str,
+        goals: usize,
+        blocks: usize,
+    },
+    /// The goals don't take exactly the movable blocks, in a level that doesn't
+    /// clear matches.
+    GoalCountMismatch {
+        stage: u16,
+        level: u16,
+        goals: usize,
+        blocks: usize,
+    },
+    /// A trigger opens a door that isn't in the map.
+    UnknownDoor {
+        stage: u16,
+        level: u16,
+        door: u16,
+    },
+    /// There should be exactly two teleporters with the id.
+    UnpairedTeleporter {
+        stage: u16,
+        level: u16,
+        id: u16,
+    },
+    /// The path is empty or two of its waypoints, including the last and the
+    /// first, aren't in the same row or column.
+    InvalidPatrol {
+        stage: u16,
+        level: u16,
+        enemy_index: usize,
+    },
+}
+
+impl fmt::Display for MapError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MapError::Parse {
+                line, path, reason, ..
+            } if *line == 0 => write!(f, "{}: {}", path, reason),
+            MapError::Parse {
+                line,
+                column,
+                path,
+                reason,
+            } => write!(f, "{} at line {} column {}: {}", path, line, column, reason),
+            MapError::MissingVersion => write!(f, "The stages have no format_version"),
+            MapError::UnsupportedVersion(version) => write!(
+                f,
+                "Format version {} is newer than {}, the newest this game reads",
+                version, FORMAT_VERSION
+            ),
+            MapError::StartOverlapsBlock {
+                stage,
+                level,
+                block_index,
+            } => write!(
+                f,
+                "Start position of stage {} level {} overlaps block {}",
+                stage, level, block_index
+            ),
+            MapError::StartInHole { stage, level } => write!(
+                f,
+                "Start position of stage {} level {} is in a hole",
+                stage, level
+            ),
+            MapError::GoalColorMismatch {
+                stage,
+                level,
+                color,
+                goals,
+                blocks,
+            } => write!(
+                f,
+                "Stage {} level {} has {} {} goals for {} {} blocks",
+                stage, level, goals, color, blocks, color
+            ),
+            MapError::GoalCountMismatch {
+                stage,
+                level,
+                goals,
+                blocks,
+            } => write!(
+                f,
+                "Stage {} level {} has {} goals for {} movable blocks",
+                stage, level, goals, blocks
+            ),
+            MapError::UnknownDoor { stage, level, door } => write!(
+                f,
+                "Stage {} level {} has a trigger for door {}, which doesn't exist",
+                stage, level, door
+            ),
+            MapError::UnpairedTeleporter { stage, level, id } => write!(
+                f,
+                "Stage {} level {} doesn't have exactly two teleporters with id {}",
+                stage, level, id
+            ),
+            MapError::InvalidPatrol {
+                stage,
+                level,
+                enemy_index,
+            } => write!(
+                f,
+                "Enemy {} of stage {} level {} needs a path of waypoints in straight lines",
+                enemy_index, stage, level
+            ),
+        }
+    }
 }
 
 #[derive(Debug, Deserialize, Serialize, Clone)]
@@ -92,20 +460,60 @@ impl Default for Size {
     }
 }
 
+/// How a block reacts to the character pushing it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BlockKind {
+    Fixed,
+    /// Moves one cell per push.
+    Movable,
+    /// Slides until it hits something.
+    Ice,
+    /// Only moves when it's pushed a second time in the same direction.
+    Heavy,
+    /// Crumbles when it's pushed.
+    Breakable,
+    /// Can only be pushed in the given direction.
+    OneWay(Direction),
+}
+
+impl Default for BlockKind {
+    fn default() -> Self {
+        BlockKind::Fixed
+    }
+}
+
+impl BlockKind {
+    /// Whether the block can be moved around, and so has to end up on a goal.
+    pub fn is_pushable(self) -> bool {
+        match self {
+            BlockKind::Fixed | BlockKind::Breakable => false,
+            _ => true,
+        }
+    }
+
+    pub fn can_be_pushed(self, direction: Direction) -> bool {
+        match self {
+            BlockKind::OneWay(one_way) => one_way == direction,
+            kind => kind.is_pushable(),
+        }
+    }
+}
+
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct Block {
-    pub can_be_moved: bool,
+    pub kind: BlockKind,
     #[serde(default)]
     pub size: Size,
 
-    #[serde(deserialize_with = "de_color")]
+    #[serde(deserialize_with = "de_color", serialize_with = "se_color")]
     pub color: Color,
 }
 
 impl Default for Block {
     fn default() -> Self {
         Block {
-            can_be_moved: false,
+            kind: BlockKind::Fixed,
             size: Size {
                 width: 50.0,
                 height: 50.0,
@@ -115,6 +523,45 @@ impl Default for Block {
     }
 }
 
+impl Block {
+    /// Draws what sets the kinds of blocks apart on top of the block.
+    pub fn draw_marker(&self, window: &mut Window, center: Vector2<f32>) {
+        let size = Vector::new(self.size.width, self.size.height);
+        let area = Rectangle::new_sized(size).with_center(center);
+        let shade = Col(Color::BLACK.with_alpha(0.6));
+
+        match self.kind {
+            BlockKind::Fixed | BlockKind::Movable => {}
+            BlockKind::Ice => window.draw(&area, Col(Color::CYAN.with_alpha(0.5))),
+            BlockKind::Heavy => window.draw(
+                &Rectangle::new_sized((size.x, size.y / 4.))
+                    .with_center(center + Vector2::new(0., size.y * 3. / 8.)),
+                shade,
+            ),
+            BlockKind::Breakable => {
+                window.draw(
+                    &Rectangle::new_sized((size.x * 0.8, 3.))
+                        .with_center(center - Vector2::new(0., size.y / 6.)),
+                    shade,
+                );
+                window.draw(
+                    &Rectangle::new_sized((3., size.y * 0.8))
+                        .with_center(center + Vector2::new(size.x / 6., 0.)),
+                    shade,
+                );
+            }
+            BlockKind::OneWay(direction) => {
+                let (x, y) = direction.offset();
+                let offset = Vector2::new(x as f32 * size.x / 3., y as f32 * size.y / 3.);
+                window.draw(
+                    &Rectangle::new_sized(size / 5.).with_center(center + offset),
+                    Col(Color::WHITE),
+                );
+            }
+        }
+    }
+}
+
 impl Component for Block {
     type Storage = HashMapStorage<Self>;
 }
@@ -164,15 +611,583 @@ pub struct Stage {
 pub struct Map {
     pub level: u16,
     pub time: u64,
+    pub start: Position,
     #[serde(rename = "blocks")]
     pub blocks_with_position: Vec<BlockAndPosition>,
+    #[serde(default)]
+    pub goals: Vec<Goal>,
+    #[serde(default)]
+    pub floor: Vec<FloorTile>,
+    #[serde(default)]
+    pub doors: Vec<Door>,
+    #[serde(default)]
+    pub triggers: Vec<Trigger>,
+    #[serde(default)]
+    pub teleporters: Vec<Teleporter>,
+    #[serde(default)]
+    pub enemies: Vec<Patrol>,
+    #[serde(default)]
+    pub collectibles: Vec<Collectible>,
+    /// Whether lines of blocks of the same color are cleared, so that a level
+    /// without goals is complete once every movable block is gone.
+    #[serde(default)]
+    pub clear_matches: bool,
+    /// Whether the level is seen from the side, with the character and the
+    /// blocks falling until they land on something.
+    #[serde(default)]
+    pub gravity: bool,
+}
+
+impl Map {
+    /// The cell of every teleporter along with the cell of its partner.
+    pub fn teleporter_partners(&self) -> HashMap<Cell, Cell> {
+        self.teleporters
+            .iter()
+            .filter_map(|teleporter| {
+                let partner = self.teleporters.iter().find(|other| {
+                    other.id == teleporter.id && other.position.0 != teleporter.position.0
+                })?;
+                Some((
+                    Cell::from_position(&teleporter.position.0),
+                    Cell::from_position(&partner.position.0),
+                ))
+            })
+            .collect()
+    }
+
+    /// Checks that the character can be placed at the start position without
+    /// ending up inside one of the blocks or falling into a hole, that every
+    /// trigger opens one of the doors, that the teleporters come in pairs, that
+    /// the enemies can walk their paths and that there's a goal for every
+    /// movable block, with no more goals of a color than blocks of it.
+    pub fn validate(&self, stage: u16, character: &Character) -> Result<(), MapError> {
+        let start = self.start.0;
+
+        let color_mismatch = COLORS
+            .iter()
+            .map(|(name, color)| {
+                let goals = self
+                    .goals
+                    .iter()
+                    .filter(|goal| goal.color == Some(*color))
+                    .count();
+                let blocks = self
+                    .blocks_with_position
+                    .iter()
+                    .filter(|block_with_position| {
+                        let block = &block_with_position.block;
+                        block.kind.is_pushable() && block.color == *color
+                    })
+                    .count();
+                (*name, goals, blocks)
+            })
+            .find(|(_name, goals, blocks)| goals > blocks);
+        if let Some((color, goals, blocks)) = color_mismatch {
+            return Err(MapError::GoalColorMismatch {
+                stage,
+                level: self.level,
+                color,
+                goals,
+                blocks,
+            });
+        }
+
+        let blocks = self
+            .blocks_with_position
+            .iter()
+            .filter(|block_with_position| block_with_position.block.kind.is_pushable())
+            .count();
+        if !self.clear_matches && self.goals.len() != blocks {
+            return Err(MapError::GoalCountMismatch {
+                stage,
+                level: self.level,
+                goals: self.goals.len(),
+                blocks,
+            });
+        }
+
+        let invalid_patrol = self.enemies.iter().position(|patrol| !patrol.is_valid());
+        if let Some(enemy_index) = invalid_patrol {
+            return Err(MapError::InvalidPatrol {
+                stage,
+                level: self.level,
+                enemy_index,
+            });
+        }
+
+        let unpaired = self.teleporters.iter().find(|teleporter| {
+            self.teleporters
+                .iter()
+                .filter(|other| other.id == teleporter.id)
+                .count()
+                != 2
+        });
+        if let Some(teleporter) = unpaired {
+            return Err(MapError::UnpairedTeleporter {
+                stage,
+                level: self.level,
+                id: teleporter.id,
+            });
+        }
+
+        let unknown_door = self
+            .triggers
+            .iter()
+            .find(|trigger| !self.doors.iter().any(|door| door.id == trigger.door));
+        if let Some(trigger) = unknown_door {
+            return Err(MapError::UnknownDoor {
+                stage,
+                level: self.level,
+                door: trigger.door,
+            });
+        }
+
+        let is_in_hole = self.floor.iter().any(|tile| {
+            tile.kind == FloorKind::Hole
+                && Cell::from_position(&tile.position.0) == Cell::from_position(&start)
+        });
+        if is_in_hole {
+            return Err(MapError::StartInHole {
+                stage,
+                level: self.level,
+            });
+        }
+
+        self.blocks_with_position
+            .iter()
+            .position(|block_with_position| {
+                let block = &block_with_position.block;
+                let position = block_with_position.position.0;
+
+                (position.x - start.x).abs() < (block.size.width + character.width) / 2.
+                    && (position.y - start.y).abs() < (block.size.height + character.height) / 2.
+            })
+            .map_or(Ok(()), |block_index| {
+                Err(MapError::StartOverlapsBlock {
+                    stage,
+                    level: self.level,
+                    block_index,
+                })
+            })
+    }
 }
 
+/// A cell a block has to be pushed onto. A colored goal only takes blocks of
+/// its color.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct Goal {
+    #[serde(flatten)]
+    pub position: Position,
+    #[serde(
+        default,
+        deserialize_with = "de_optional_color",
+        serialize_with = "se_optional_color",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub color: Option<Color>,
+}
+
+impl Goal {
+    /// The palette index of the color of the blocks it takes, if it's colored.
+    pub fn paint(&self) -> Option<usize> {
+        self.color.as_ref().map(palette_index)
+    }
+}
+
+/// What the floor of a cell is made of, where it isn't plain floor.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FloorKind {
+    /// The character and the blocks keep sliding over it.
+    Ice,
+    /// Moves whatever stands on it a cell in the given direction every so often.
+    Conveyor(Direction),
+    /// The character falls into it, a block pushed into it fills it.
+    Hole,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct FloorTile {
+    pub kind: FloorKind,
+    pub position: Position,
+}
+
+impl FloorKind {
+    pub fn draw(self, window: &mut Window, center: Vector2<f32>) {
+        let size = Vector::new(GRID_SIZE, GRID_SIZE);
+        let area = Rectangle::new_sized(size).with_center(center);
+
+        match self {
+            FloorKind::Ice => window.draw(&area, Col(Color::CYAN.with_alpha(0.25))),
+            FloorKind::Conveyor(direction) => {
+                window.draw(&area, Col(Color::WHITE.with_alpha(0.15)));
+
+                let (x, y) = direction.offset();
+                [-1., 0., 1.].iter().for_each(|step| {
+                    let offset =
+                        Vector2::new(x as f32 * step * size.x / 3., y as f32 * step * size.y / 3.);
+                    window.draw(
+                        &Rectangle::new_sized(size / 8.).with_center(center + offset),
+                        Col(Color::WHITE.with_alpha(0.5)),
+                    );
+                });
+            }
+            FloorKind::Hole => {
+                window.draw(&area, Col(Color::WHITE.with_alpha(0.2)));
+                window.draw(
+                    &Rectangle::new_sized(size * 0.8).with_center(center),
+                    Col(Color::BLACK),
+                );
+            }
+        }
+    }
+}
+
+/// The color of the things linked by an id, like a door and the triggers that
+/// open it.
+fn link_color(id: u16) -> Color {
+    COLORS[id as usize % COLORS.len()].1
+}
+
+/// A wall that's open while one of the triggers linked to it by its id is
+/// active.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct Door {
+    pub id: u16,
+    pub position: Position,
+}
+
+impl Door {
+    pub fn draw(&self, window: &mut Window, is_open: bool) {
+        let size = Vector::new(GRID_SIZE, GRID_SIZE);
+        let center = self.position.0;
+        let color = link_color(self.id);
+
+        if is_open {
+            window.draw(
+                &Rectangle::new_sized(size).with_center(center),
+                Col(color.with_alpha(0.2)),
+            );
+        } else {
+            window.draw(&Rectangle::new_sized(size).with_center(center), Col(color));
+            [-1., 0., 1.].iter().for_each(|step| {
+                window.draw(
+                    &Rectangle::new_sized((3., size.y * 0.8))
+                        .with_center(center + Vector2::new(step * size.x / 4., 0.)),
+                    Col(Color::BLACK.with_alpha(0.6)),
+                );
+            });
+        }
+    }
+}
+
+/// What makes a trigger active.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TriggerKind {
+    /// Active while a block or the character stands on it.
+    PressurePlate,
+    /// Turned on and off by the character stepping on it.
+    Switch,
+    /// Taken by the character stepping on it, which opens its door for good.
+    Key,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct Trigger {
+    pub kind: TriggerKind,
+    /// The id of the door it opens.
+    pub door: u16,
+    pub position: Position,
+    /// Whether a switch is on or a key has been taken.
+    #[serde(default)]
+    pub active: bool,
+}
+
+impl Trigger {
+    pub fn draw(&self, window: &mut Window) {
+        let size = Vector::new(GRID_SIZE, GRID_SIZE);
+        let center = self.position.0;
+        let color = link_color(self.door);
+
+        match self.kind {
+            TriggerKind::PressurePlate => {
+                let alpha = if self.active { 0.8 } else { 0.4 };
+                window.draw(
+                    &Rectangle::new_sized(size * 0.7).with_center(center),
+                    Col(color.with_alpha(alpha)),
+                );
+            }
+            TriggerKind::Switch => {
+                window.draw(
+                    &Rectangle::new_sized(size * 0.4).with_center(center),
+                    Col(color),
+                );
+                let offset = if self.active {
+                    -size.y / 8.
+                } else {
+                    size.y / 8.
+                };
+                window.draw(
+                    &Rectangle::new_sized(size / 5.).with_center(center + Vector2::new(0., offset)),
+                    Col(Color::WHITE),
+                );
+            }
+            TriggerKind::Key if self.active => {}
+            TriggerKind::Key => {
+                window.draw(
+                    &Circle::new(center - Vector2::new(size.x / 8., 0.), size.x / 8.),
+                    Col(color),
+                );
+                window.draw(
+                    &Rectangle::new_sized((size.x / 3., size.y / 12.))
+                        .with_center(center + Vector2::new(size.x / 8., 0.)),
+                    Col(color),
+                );
+            }
+        }
+    }
+}
+
+/// Moves the character or a pushed block that lands on it to the other
+/// teleporter with the same id, if nothing is there.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct Teleporter {
+    pub id: u16,
+    pub position: Position,
+}
+
+impl Teleporter {
+    pub fn draw(&self, window: &mut Window) {
+        let center = self.position.0;
+
+        window.draw(
+            &Circle::new(center, GRID_SIZE * 0.4),
+            Col(link_color(self.id)),
+        );
+        window.draw(&Circle::new(center, GRID_SIZE * 0.25), Col(Color::BLACK));
+    }
+}
+
+/// What the character gets for picking up a collectible.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CollectibleKind {
+    /// Counts towards the score.
+    Gem,
+    /// Adds the given milliseconds to the time of the level.
+    Clock(u64),
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct Collectible {
+    pub kind: CollectibleKind,
+    pub position: Position,
+}
+
+impl Component for Collectible {
+    type Storage = HashMapStorage<Self>;
+}
+
+impl Collectible {
+    pub fn draw(&self, window: &mut Window) {
+        let center = self.position.0;
+
+        match self.kind {
+            CollectibleKind::Gem => {
+                window.draw(&Circle::new(center, GRID_SIZE / 5.), Col(Color::PURPLE));
+                window.draw(
+                    &Circle::new(center - Vector2::new(3., 3.), GRID_SIZE / 15.),
+                    Col(Color::WHITE.with_alpha(0.7)),
+                );
+            }
+            CollectibleKind::Clock(_bonus) => {
+                window.draw(&Circle::new(center, GRID_SIZE / 5.), Col(Color::WHITE));
+                window.draw(
+                    &Rectangle::new_sized((2., GRID_SIZE / 6.))
+                        .with_center(center - Vector2::new(0., GRID_SIZE / 12.)),
+                    Col(Color::BLACK),
+                );
+                window.draw(
+                    &Rectangle::new_sized((GRID_SIZE / 8., 2.))
+                        .with_center(center + Vector2::new(GRID_SIZE / 16., 0.)),
+                    Col(Color::BLACK),
+                );
+            }
+        }
+    }
+}
+
+fn default_patrol_speed() -> f32 {
+    1.
+}
+
+/// An enemy that walks from each waypoint of its path to the next in a straight
+/// line, and from the last one back to the first.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct Patrol {
+    pub path: Vec<Position>,
+    /// In cells per second.
+    #[serde(default = "default_patrol_speed")]
+    pub speed: f32,
+}
+
+impl Patrol {
+    pub fn cells(&self) -> Vec<Cell> {
+        self.path
+            .iter()
+            .map(|waypoint| Cell::from_position(&waypoint.0))
+            .collect()
+    }
+
+    pub fn is_valid(&self) -> bool {
+        let cells = self.cells();
+
+        !cells.is_empty()
+            && self.speed > 0.
+            && cells
+                .iter()
+                .zip(cells.iter().cycle().skip(1))
+                .all(|(from, to)| from.x == to.x || from.y == to.y)
+    }
+
+    /// Draws the waypoints and the lines between them.
+    pub fn draw_path(&self, window: &mut Window) {
+        let color = Col(Color::RED.with_alpha(0.3));
+
+        self.path
+            .iter()
+            .zip(self.path.iter().cycle().skip(1))
+            .for_each(|(from, to)| {
+                let min = Vector2::new(from.0.x.min(to.0.x), from.0.y.min(to.0.y));
+                let max = Vector2::new(from.0.x.max(to.0.x), from.0.y.max(to.0.y));
+                window.draw(
+                    &Rectangle::new(min - Vector2::new(2., 2.), max - min + Vector2::new(4., 4.)),
+                    color,
+                );
+                window.draw(
+                    &Rectangle::new_sized((GRID_SIZE / 4., GRID_SIZE / 4.)).with_center(from.0),
+                    color,
+                );
+            });
+    }
+}
+
+/// The level that is being played, with the board used for solving it.
+#[derive(Debug)]
+pub struct Level {
+    pub stage: u16,
+    pub map: Map,
+    pub board: Board,
+}
+
+impl Level {
+    pub fn new(stage: u16, map: Map) -> Self {
+        let board = Board::from_map(&map, Bounds::default());
+        Level { stage, map, board }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct CurrentLevel(pub Option<Level>);
+
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct BlockAndPosition {
     #[serde(flatten)]
     pub block: Block,
 
-    #[serde(flatten)]
     pub position: Position,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn map_with(blocks: Value, goals: Value) -> Map {
+        serde_json::from_value(json!({
+            "level": 1,
+            "time": 30000,
+            "start": {"x": 100, "y": 300},
+            "blocks": blocks,
+            "goals": goals,
+        }))
+        .unwrap()
+    }
+
+    fn validate(map: &Map) -> Result<(), MapError> {
+        map.validate(1, &Character::default())
+    }
+
+    #[test]
+    fn accepts_colored_goals_next_to_uncolored_ones() {
+        let map = map_with(
+            json!([
+                {"kind": "movable", "color": "red", "position": {"x": 250, "y": 300}},
+                {"kind": "movable", "color": "blue", "position": {"x": 250, "y": 400}},
+            ]),
+            json!([
+                {"x": 400, "y": 300, "color": "red"},
+                {"x": 400, "y": 400},
+            ]),
+        );
+
+        assert!(validate(&map).is_ok());
+    }
+
+    #[test]
+    fn accepts_uncolored_goals_only() {
+        let map = map_with(
+            json!([
+                {"kind": "movable", "color": "red", "position": {"x": 250, "y": 300}},
+                {"kind": "movable", "color": "blue", "position": {"x": 250, "y": 400}},
+            ]),
+            json!([{"x": 400, "y": 300}, {"x": 400, "y": 400}]),
+        );
+
+        assert!(validate(&map).is_ok());
+    }
+
+    #[test]
+    fn rejects_more_goals_of_a_color_than_blocks() {
+        let map = map_with(
+            json!([
+                {"kind": "movable", "color": "red", "position": {"x": 250, "y": 300}},
+                {"kind": "movable", "color": "blue", "position": {"x": 250, "y": 400}},
+            ]),
+            json!([
+                {"x": 400, "y": 300, "color": "red"},
+                {"x": 400, "y": 400, "color": "red"},
+            ]),
+        );
+
+        match validate(&map) {
+            Err(MapError::GoalColorMismatch {
+                color: "red",
+                goals: 2,
+                blocks: 1,
+                ..
+            }) => (),
+            result => panic!("Expected a color mismatch, got {:?}", result),
+        }
+    }
+
+    #[test]
+    fn rejects_a_block_without_a_goal() {
+        let map = map_with(
+            json!([
+                {"kind": "movable", "color": "red", "position": {"x": 250, "y": 300}},
+                {"kind": "movable", "color": "blue", "position": {"x": 250, "y": 400}},
+            ]),
+            json!([{"x": 400, "y": 300, "color": "red"}]),
+        );
+
+        match validate(&map) {
+            Err(MapError::GoalCountMismatch {
+                goals: 1,
+                blocks: 2,
+                ..
+            }) => (),
+            result => panic!("Expected a goal count mismatch, got {:?}", result),
+        }
+    }
+}
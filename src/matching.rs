@@ -0,0 +1,80 @@
+use collision::{BlockHandle, Collision};
+use map::{Block, CurrentLevel};
+use physics::Position;
+use push::PushHistory;
+use score::Score;
+use solver::{self, Cell};
+use specs::{Entities, Join, Read, ReadStorage, System, Write};
+use utils::palette_index;
+
+/// Clears the blocks that are in a line with enough blocks of the same color,
+/// along with their collision objects, in the levels that clear matches.
+pub struct MatchSystem;
+
+impl<'a> System<'a> for MatchSystem {
+    type SystemData = (
+        Entities<'a>,
+        Read<'a, CurrentLevel>,
+        ReadStorage<'a, Block>,
+        ReadStorage<'a, BlockHandle>,
+        ReadStorage<'a, Position>,
+        Write<'a, Collision>,
+        Write<'a, PushHistory>,
+        Write<'a, Score>,
+    );
+
+    fn run(
+        &mut self,
+        (
+            entities,
+            current_level,
+            blocks,
+            block_handles,
+            positions,
+            mut collision,
+            mut push_history,
+            mut score,
+        ): Self::SystemData,
+    ) {
+        let clears_matches = current_level
+            .0
+            .as_ref()
+            .map_or(false, |level| level.board.clears_matches);
+        if !clears_matches {
+            return;
+        }
+
+        let painted_blocks = (&blocks, &positions)
+            .join()
+            .map(|(block, position)| {
+                (
+                    Cell::from_position(&position.0),
+                    block.kind,
+                    palette_index(&block.color),
+                )
+            })
+            .collect::<Vec<_>>();
+        let matched = solver::matched_blocks(&painted_blocks);
+        if matched.is_empty() {
+            return;
+        }
+
+        info!("Clearing {} matched blocks", matched.len());
+        score.cleared += matched.len() as u32;
+
+        (&entities, &blocks, &positions)
+            .join()
+            .filter(|(_entity, block, position)| {
+                block.kind.is_pushable() && matched.contains(&Cell::from_position(&position.0))
+            })
+            .for_each(|(entity, block, _position)| {
+                if let Some(block_handle) = block_handles.get(entity) {
+                    if let Some(ref mut collision_world) = collision.world {
+                        collision_world.remove(&[block_handle.0]);
+                    }
+                }
+                let _ = entities.delete(entity);
+                push_history.record_removed(entity, block.to_owned());
+            });
+    }
+}
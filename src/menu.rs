@@ -0,0 +1,222 @@
+use hud::CachedText;
+use map::Stage;
+use quicksilver::{
+    geom::{Rectangle, Shape, Vector},
+    graphics::{Background::Col, Color, Font, FontStyle},
+    input::{ButtonState, Key, MouseButton},
+    lifecycle::Window,
+    Result,
+};
+use save::{BestRun, LevelId, SaveGame};
+use HEADER_HEIGHT;
+use WINDOW_HEIGHT;
+use WINDOW_WIDTH;
+
+const ROW_HEIGHT: f32 = 40.;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LevelStatus {
+    Locked,
+    Unlocked,
+    Completed,
+}
+
+#[derive(Debug, Clone)]
+pub struct MenuEntry {
+    pub id: LevelId,
+    pub status: LevelStatus,
+    pub best_run: Option<BestRun>,
+}
+
+impl MenuEntry {
+    /// Lists every level in the stages, in stage and level order.
+    pub fn from_stages(stages: &[Stage], save_game: &SaveGame) -> Vec<MenuEntry> {
+        let mut entries = stages
+            .iter()
+            .flat_map(|stage| {
+                stage.maps.iter().map(move |map| {
+                    let id = LevelId::new(stage.stage, map.level);
+                    let record = save_game.record(id);
+                    let status = if record.map_or(false, |record| record.completed) {
+                        LevelStatus::Completed
+                    } else if save_game.is_unlocked(id) {
+                        LevelStatus::Unlocked
+                    } else {
+                        LevelStatus::Locked
+                    };
+
+                    MenuEntry {
+                        id,
+                        status,
+                        best_run: record.and_then(|record| record.best_run),
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+
+        entries.sort_by_key(|entry| (entry.id.stage, entry.id.level));
+        entries
+    }
+
+    fn label(&self) -> String {
+        let status = match self.status {
+            LevelStatus::Locked => "Locked".to_owned(),
+            LevelStatus::Unlocked => "".to_owned(),
+            LevelStatus::Completed => match self.best_run {
+                Some(best_run) => {
+                    format!("Best: {} moves, {} pushes", best_run.steps, best_run.pushes)
+                }
+                None => "Completed".to_owned(),
+            },
+        };
+
+        format!(
+            "Stage {} level {}  {}",
+            self.id.stage, self.id.level, status
+        )
+    }
+}
+
+pub struct Menu {
+    pub selected: usize,
+    title: CachedText,
+    /// The label of every entry along with the status it's colored by.
+    rows: Vec<(LevelStatus, CachedText)>,
+}
+
+impl Menu {
+    pub fn new(selected: usize) -> Self {
+        Menu {
+            selected,
+            title: CachedText::new("Levels", FontStyle::new(48.0, Color::WHITE)),
+            rows: vec![],
+        }
+    }
+
+    fn visible_rows() -> usize {
+        ((f32::from(WINDOW_HEIGHT) - HEADER_HEIGHT) / ROW_HEIGHT) as usize
+    }
+
+    fn first_visible_row(&self) -> usize {
+        (self.selected + 1).saturating_sub(Self::visible_rows())
+    }
+
+    fn row_area(&self, index: usize) -> Rectangle {
+        let row = (index - self.first_visible_row()) as f32;
+
+        Rectangle::new(
+            (50., HEADER_HEIGHT + row * ROW_HEIGHT),
+            (f32::from(WINDOW_WIDTH) - 100., ROW_HEIGHT - 4.),
+        )
+    }
+
+    fn visible_entries<'a>(
+        &self,
+        entries: &'a [MenuEntry],
+    ) -> impl Iterator<Item = (usize, &'a MenuEntry)> {
+        entries
+            .iter()
+            .enumerate()
+            .skip(self.first_visible_row())
+            .take(Self::visible_rows())
+    }
+
+    /// Moves the selection with the arrow keys or the mouse. Returns the level to
+    /// play when an unlocked level is chosen with enter or a click.
+    pub fn handle_input(&mut self, entries: &[MenuEntry], window: &Window) -> Option<LevelId> {
+        if entries.is_empty() {
+            return None;
+        }
+
+        let is_pressed = |key: Key| window.keyboard()[key] == ButtonState::Pressed;
+
+        if is_pressed(Key::Up) {
+            self.selected = self.selected.saturating_sub(1);
+        }
+        if is_pressed(Key::Down) {
+            self.selected = (self.selected + 1).min(entries.len() - 1);
+        }
+
+        let mouse = window.mouse();
+        let hovered = self
+            .visible_entries(entries)
+            .find(|(index, _entry)| self.row_area(*index).contains(mouse.pos()))
+            .map(|(index, _entry)| index);
+
+        let mut is_chosen = is_pressed(Key::Return);
+        if let Some(hovered) = hovered {
+            if mouse[MouseButton::Left] == ButtonState::Pressed {
+                self.selected = hovered;
+                is_chosen = true;
+            }
+        }
+
+        self.selected = self.selected.min(entries.len() - 1);
+        let entry = &entries[self.selected];
+
+        if is_chosen && entry.status != LevelStatus::Locked {
+            Some(entry.id)
+        } else {
+            None
+        }
+    }
+
+    /// Keeps a label for every entry, starting over on the ones whose status
+    /// changed as that changes their color.
+    fn update_rows(&mut self, entries: &[MenuEntry]) {
+        self.rows.truncate(entries.len());
+
+        entries.iter().enumerate().for_each(|(index, entry)| {
+            let label = entry.label();
+            let has_same_status = self
+                .rows
+                .get(index)
+                .map_or(false, |(status, _text)| *status == entry.status);
+
+            if has_same_status {
+                self.rows[index].1.set_text(&label);
+            } else {
+                let color = match entry.status {
+                    LevelStatus::Locked => Color::WHITE.with_alpha(0.4),
+                    LevelStatus::Unlocked => Color::WHITE,
+                    LevelStatus::Completed => Color::GREEN,
+                };
+                let row = (
+                    entry.status,
+                    CachedText::new(&label, FontStyle::new(24.0, color)),
+                );
+
+                if index < self.rows.len() {
+                    self.rows[index] = row;
+                } else {
+                    self.rows.push(row);
+                }
+            }
+        });
+    }
+
+    pub fn draw(&mut self, entries: &[MenuEntry], window: &mut Window, font: &Font) -> Result<()> {
+        self.update_rows(entries);
+
+        self.title.draw(window, font, |_area| {
+            Vector::new(f32::from(WINDOW_WIDTH) / 2., 50.)
+        })?;
+
+        let visible_rows = self
+            .visible_entries(entries)
+            .map(|(index, _entry)| (index, self.row_area(index)))
+            .collect::<Vec<_>>();
+        let selected = self.selected;
+
+        visible_rows
+            .into_iter()
+            .map(|(index, area)| {
+                if index == selected {
+                    window.draw(&area, Col(Color::WHITE.with_alpha(0.2)));
+                }
+
+                self.rows[index].1.draw(window, font, |_area| area.center())
+            })
+            .collect()
+    }
+}
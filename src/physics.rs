@@ -1,4 +1,5 @@
 use character::Character;
+use collectible::Pickups;
 use collision::{Collision, CollisionObjectData};
 use nalgebra::{Isometry2, Vector2};
 use ncollide2d::{
@@ -8,6 +9,7 @@ use ncollide2d::{
         CollisionGroups, CollisionObject, CollisionObjectHandle, CollisionWorld, GeometricQueryType,
     },
 };
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use specs::{
     Component, Entities, Join, LazyUpdate, Read, ReadStorage, System, VecStorage, Write,
     WriteStorage,
@@ -122,35 +124,39 @@ impl PhysicsSystem {
         position_storage: &ReadStorage<'a, Position>,
         character_storage: &ReadStorage<'a, Character>,
     ) {
-        let isometry_positions = (entities, position_storage)
+        (entities, position_storage)
             .join()
-            .map(|(entity, position)| {
-                if let None = character_storage.get(entity) {
-                    println!("BLOCK POS {:?}", position);
-                    Some(Isometry2::new(position.0, nalgebra::zero()))
-                } else {
-                    None
-                }
-            })
-            .filter_map(|x| x)
-            .collect::<Vec<Isometry2<_>>>();
+            .filter(|(entity, _position)| character_storage.get(*entity).is_none())
+            .for_each(|(_entity, position)| {
+                debug!("Adding the collision object of the block at {:?}", position);
+                Self::add_block_handle(settings, collision, position);
+            });
 
-        let character_position = (velocity_storage, character_storage, position_storage)
-            .join()
-            .map(|(_velocity, _character, position)| {
-                let character_position = position.0;
-                //println!("Character pos {:?}", position);
-                Isometry2::new(
-                    Vector2::new(character_position.x, character_position.y),
-                    nalgebra::zero(),
-                )
-            })
-            .collect::<Vec<Isometry2<_>>>();
+        if let Some((_velocity, _character, position)) =
+            (velocity_storage, character_storage, position_storage)
+                .join()
+                .next()
+        {
+            Self::place_character_handle(settings, collision, position);
+        }
+    }
 
-        // The character is part of group 1 and can interact with everything.
-        let mut character_groups = CollisionGroups::new();
-        character_groups.set_membership(&[1]);
+    /// Adds a collision object for a block at the given position.
+    pub fn add_block_handle(
+        settings: &Settings,
+        collision: &mut Collision,
+        position: &Position,
+    ) -> Option<CollisionObjectHandle> {
+        Self::add_rect_handle(settings.block_size.x, collision, position)
+    }
 
+    /// Adds a square collision object, like the ones of the blocks, with the
+    /// given half extent.
+    pub fn add_rect_handle(
+        half_extent: f32,
+        collision: &mut Collision,
+        position: &Position,
+    ) -> Option<CollisionObjectHandle> {
         // All the other objects are part of the group 2 and interact only with the character (but not with
         // each other).
         let mut others_groups = CollisionGroups::new();
@@ -158,12 +164,9 @@ impl PhysicsSystem {
         others_groups.set_whitelist(&[1]);
 
         let rect_data = CollisionObjectData::new("rect", None);
-        let character_data = CollisionObjectData::new("character", Some(Vector2::new(32.0, 12.0)));
 
         let margin = 2.0;
-        let rect_half_extent = settings.block_size.x - margin;
-        let character_half_extent_width = settings.character_size.x - margin;
-        let character_half_extent_height = settings.character_size.y - margin;
+        let rect_half_extent = half_extent - margin;
 
         let contacts_query = GeometricQueryType::Contacts(margin, 0.);
         let rect = ShapeHandle::new(Cuboid::new(Vector2::new(
@@ -171,6 +174,92 @@ impl PhysicsSystem {
             rect_half_extent,
         )));
 
+        collision.world.as_mut().map(|world| {
+            world.add(
+                Isometry2::new(position.0, nalgebra::zero()),
+                rect,
+                others_groups,
+                contacts_query,
+                rect_data,
+            )
+        })
+    }
+
+    /// Adds the collision object of a collectible, which is part of the group 3
+    /// and only touches the character.
+    pub fn add_collectible_handle(
+        collision: &mut Collision,
+        position: &Position,
+    ) -> Option<CollisionObjectHandle> {
+        let mut collectible_groups = CollisionGroups::new();
+        collectible_groups.set_membership(&[3]);
+        collectible_groups.set_whitelist(&[1]);
+
+        let collectible_data = CollisionObjectData::new("collectible", None);
+        let contacts_query = GeometricQueryType::Contacts(0., 0.);
+        let shape = ShapeHandle::new(Cuboid::new(Vector2::new(10., 10.)));
+
+        collision.world.as_mut().map(|world| {
+            world.add(
+                Isometry2::new(position.0, nalgebra::zero()),
+                shape,
+                collectible_groups,
+                contacts_query,
+                collectible_data,
+            )
+        })
+    }
+
+    /// The collectible the character touched, if the contact is with one.
+    fn touched_collectible(
+        collision: &Collision,
+        contact_event: &ContactEvent,
+    ) -> Option<CollisionObjectHandle> {
+        let world = collision.world.as_ref()?;
+        let (first, second) = match contact_event {
+            ContactEvent::Started(first, second) => (*first, *second),
+            ContactEvent::Stopped(_, _) => return None,
+        };
+
+        [first, second].iter().cloned().find(|handle| {
+            world
+                .collision_object(*handle)
+                .map_or(false, |object| object.data().name == "collectible")
+        })
+    }
+
+    pub fn set_block_handle_position(
+        collision: &mut Collision,
+        handle: CollisionObjectHandle,
+        position: &Position,
+    ) {
+        if let Some(ref mut world) = collision.world {
+            world.set_position(handle, Isometry2::new(position.0, nalgebra::zero()));
+        }
+    }
+
+    /// Moves the character's collision object to the given position, adding it to the
+    /// collision world first if it isn't there yet.
+    pub fn place_character_handle(
+        settings: &Settings,
+        collision: &mut Collision,
+        position: &Position,
+    ) {
+        let character_position =
+            Isometry2::new(Vector2::new(position.0.x, position.0.y), nalgebra::zero());
+
+        // The character is part of group 1 and can interact with everything.
+        let mut character_groups = CollisionGroups::new();
+        character_groups.set_membership(&[1]);
+
+        let character_data = CollisionObjectData::new("character", Some(Vector2::new(32.0, 12.0)));
+
+        let margin = 2.0;
+        let character_half_extent_width = settings.character_size.x - margin;
+        let character_half_extent_height = settings.character_size.y - margin;
+
+        let contacts_query = GeometricQueryType::Contacts(margin, 0.);
+
         // TODO: When Capsule implements Shape we should use it instead of a Cuboid.
         // https://github.com/rustsim/ncollide/issues/175
         let character = ShapeHandle::new(Cuboid::new(Vector2::new(
@@ -178,37 +267,31 @@ impl PhysicsSystem {
             character_half_extent_height,
         )));
 
-        let character_position: Option<Isometry2<f32>> =
-            character_position.get(0).map(|opt| opt.to_owned());
-
         let character_handle = if let Some(ref mut world) = collision.world {
-            let handle = character_position.map(|character_position| {
-                println!("Char pos {:?}", character_position);
-                world.add(
-                    character_position,
-                    character,
-                    character_groups,
-                    contacts_query,
-                    character_data,
-                )
-            });
-
-            isometry_positions.iter().for_each(|position| {
-                println!("iso pos {:?}", position);
-                world.add(
-                    *position,
-                    rect.clone(),
-                    others_groups,
-                    contacts_query,
-                    rect_data.clone(),
-                );
-            });
-            handle
+            match collision.character_handle {
+                Some(character_handle) => {
+                    world.set_position(character_handle, character_position);
+                    Some(character_handle)
+                }
+                None => {
+                    debug!(
+                        "Adding the character collision object at {:?}",
+                        character_position
+                    );
+                    Some(world.add(
+                        character_position,
+                        character,
+                        character_groups,
+                        contacts_query,
+                        character_data,
+                    ))
+                }
+            }
         } else {
             None
         };
 
-        collision.character_position = character_position;
+        collision.character_position = Some(character_position);
         collision.character_handle = character_handle;
     }
 
@@ -292,6 +375,7 @@ impl<'a> System<'a> for PhysicsSystem {
         WriteStorage<'a, Position>,
         WriteStorage<'a, CollisionHandle>,
         Read<'a, LazyUpdate>,
+        Write<'a, Pickups>,
     );
 
     fn run(&mut self, data: Self::SystemData) {
@@ -304,6 +388,7 @@ impl<'a> System<'a> for PhysicsSystem {
             mut position_storage,
             _,
             updater,
+            mut pickups,
         ) = data;
 
         (&entities, &velocity_storage, &mut position_storage)
@@ -316,6 +401,14 @@ impl<'a> System<'a> for PhysicsSystem {
                     let collision_events = Self::update_collision(position, &mut collision_world);
 
                     collision_events.into_iter().for_each(|event| {
+                        // Collectibles are picked up instead of stopping the character.
+                        if let Some(handle) =
+                            Self::touched_collectible(&collision_world, &event.contact_event)
+                        {
+                            pickups.0.push(handle);
+                            return;
+                        }
+
                         match event.contact_event {
                             ContactEvent::Started(_, _) => {
                                 println!("Velocity: {:?}", velocity);
@@ -338,9 +431,39 @@ impl<'a> System<'a> for PhysicsSystem {
     }
 }
 
-#[derive(Debug, Deserialize, Serialize, Clone)]
+#[derive(Debug, Clone)]
 pub struct Position(pub Vector2<f32>);
 
+/// The map files describe positions as `{"x": 150, "y": 200}`.
+#[derive(Deserialize, Serialize)]
+struct PositionDef {
+    x: f32,
+    y: f32,
+}
+
+impl Serialize for Position {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        PositionDef {
+            x: self.0.x,
+            y: self.0.y,
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Position {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        PositionDef::deserialize(deserializer)
+            .map(|position| Position(Vector2::new(position.x, position.y)))
+    }
+}
+
 impl Component for Position {
     type Storage = VecStorage<Self>;
 }
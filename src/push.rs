@@ -0,0 +1,328 @@
+use character::Character;
+use collision::{BlockHandle, Collision};
+use enemy::Enemy;
+use floor::Floor;
+use map::{self, Block, BlockAndPosition, BlockKind, CurrentLevel, Trigger};
+use physics::{PhysicsSystem, Position, Velocity};
+use solver::{Cell, Direction};
+use specs::{Entities, Entity, Join, Read, ReadStorage, System, World, Write, WriteStorage};
+use trigger::Triggers;
+use Settings;
+
+/// How far from touching the character can be and still push a block.
+const PUSH_MARGIN: f32 = 4.;
+
+#[derive(Debug, Clone, Copy)]
+pub struct PushEvent {
+    pub entity: Entity,
+    pub from: Cell,
+    pub to: Cell,
+    pub direction: Direction,
+}
+
+/// The pushes made during the last dispatch.
+#[derive(Debug, Default)]
+pub struct PushEvents(pub Vec<PushEvent>);
+
+/// Where the character and the pushable blocks were before a push, along with
+/// the blocks that were broken or fell into a hole, the floor and the triggers.
+#[derive(Debug, Clone)]
+pub struct Snapshot {
+    pub character: Position,
+    pub blocks: Vec<(Entity, Position)>,
+    pub removed: Vec<BlockAndPosition>,
+    pub floor: Floor,
+    pub triggers: Vec<Trigger>,
+}
+
+#[derive(Debug, Default)]
+pub struct PushHistory(pub Vec<Snapshot>);
+
+impl PushHistory {
+    /// Keeps a block that was removed after the last push, so that undoing the
+    /// push brings it back where it was before it.
+    pub fn record_removed(&mut self, entity: Entity, block: Block) {
+        if let Some(snapshot) = self.0.last_mut() {
+            if let Some(index) = snapshot
+                .blocks
+                .iter()
+                .position(|(snapshot_entity, _position)| *snapshot_entity == entity)
+            {
+                let (_entity, position) = snapshot.blocks.remove(index);
+                snapshot.removed.push(BlockAndPosition { block, position });
+            }
+        }
+    }
+}
+
+/// The heavy block that has been pushed once and in which direction, and whether
+/// the character is still pushing against it.
+#[derive(Debug, Default)]
+pub struct Strain {
+    pub block: Option<(Entity, Direction)>,
+    pub is_pushing: bool,
+}
+
+pub struct PushSystem;
+
+impl PushSystem {
+    pub fn direction_from_velocity(velocity: &Velocity) -> Option<Direction> {
+        let velocity = velocity.0;
+
+        if velocity.x == 0. && velocity.y == 0. {
+            None
+        } else if velocity.x.abs() > velocity.y.abs() {
+            if velocity.x > 0. {
+                Some(Direction::Right)
+            } else {
+                Some(Direction::Left)
+            }
+        } else if velocity.y > 0. {
+            Some(Direction::Down)
+        } else {
+            Some(Direction::Up)
+        }
+    }
+
+    /// Checks whether the character is right next to the block and walking into it.
+    fn is_touching(
+        character: &Character,
+        character_position: &Position,
+        block: &Block,
+        block_position: &Position,
+        direction: Direction,
+    ) -> bool {
+        let (x, y) = direction.offset();
+        let offset = block_position.0 - character_position.0;
+        let along = offset.x * x as f32 + offset.y * y as f32;
+        let across = (offset.x * y as f32).abs() + (offset.y * x as f32).abs();
+        let reach = match direction {
+            Direction::Left | Direction::Right => (character.width + block.size.width) / 2.,
+            Direction::Up | Direction::Down => (character.height + block.size.height) / 2.,
+        };
+
+        along > 0.
+            && along <= reach + PUSH_MARGIN
+            && across < block.size.width.min(block.size.height) / 2.
+    }
+
+    /// Heavy blocks only move when the character lets go after the first push
+    /// and pushes them again in the same direction.
+    fn is_second_push(
+        strain: &mut Strain,
+        was_pushing: bool,
+        entity: Entity,
+        direction: Direction,
+    ) -> bool {
+        let is_strained = strain.block == Some((entity, direction));
+        strain.is_pushing = true;
+
+        if is_strained && !was_pushing {
+            strain.block = None;
+            true
+        } else {
+            if !is_strained {
+                debug!("Straining against heavy block {:?}", entity);
+            }
+            strain.block = Some((entity, direction));
+            false
+        }
+    }
+}
+
+impl<'a> System<'a> for PushSystem {
+    type SystemData = (
+        Entities<'a>,
+        Read<'a, CurrentLevel>,
+        ReadStorage<'a, Character>,
+        ReadStorage<'a, Velocity>,
+        ReadStorage<'a, Block>,
+        ReadStorage<'a, BlockHandle>,
+        ReadStorage<'a, Enemy>,
+        WriteStorage<'a, Position>,
+        Write<'a, Collision>,
+        Write<'a, PushEvents>,
+        Write<'a, PushHistory>,
+        Write<'a, Strain>,
+        Read<'a, Floor>,
+        Read<'a, Triggers>,
+    );
+
+    fn run(
+        &mut self,
+        (
+            entities,
+            current_level,
+            characters,
+            velocities,
+            blocks,
+            block_handles,
+            enemies,
+            mut positions,
+            mut collision,
+            mut push_events,
+            mut push_history,
+            mut strain,
+            floor,
+            triggers,
+        ): Self::SystemData,
+    ) {
+        push_events.0.clear();
+
+        let was_pushing = strain.is_pushing;
+        strain.is_pushing = false;
+
+        let board = match current_level.0 {
+            Some(ref level) => &level.board,
+            None => return,
+        };
+
+        let moving_character = (&characters, &velocities, &positions)
+            .join()
+            .next()
+            .and_then(|(character, velocity, position)| {
+                Self::direction_from_velocity(velocity)
+                    .map(|direction| (character, position.to_owned(), direction))
+            });
+
+        let (character, character_position, direction) = match moving_character {
+            Some(moving_character) => moving_character,
+            None => return,
+        };
+        // With gravity, jumping or falling into a block doesn't push it.
+        if board.has_gravity && (direction == Direction::Up || direction == Direction::Down) {
+            return;
+        }
+
+        let pushed_block = (&entities, &blocks, &positions)
+            .join()
+            .filter(|(_entity, block, _position)| block.kind != BlockKind::Fixed)
+            .find(|(_entity, block, position)| {
+                Self::is_touching(character, &character_position, block, position, direction)
+            })
+            .map(|(entity, block, position)| (entity, block.to_owned(), position.to_owned()));
+
+        let (entity, block, block_position) = match pushed_block {
+            Some(pushed_block) => pushed_block,
+            None => return,
+        };
+        let kind = block.kind;
+        let from = Cell::from_position(&block_position.0);
+
+        let to = {
+            let is_free = |cell: &Cell| {
+                board.is_free(cell)
+                    && !triggers.is_closed(cell)
+                    && !(&blocks, &positions)
+                        .join()
+                        .any(|(_block, position)| Cell::from_position(&position.0) == *cell)
+                    && !(&enemies, &positions)
+                        .join()
+                        .any(|(enemy, position)| enemy.is_in(position, cell))
+            };
+
+            if kind == BlockKind::Breakable {
+                from
+            } else {
+                let mut to = from.step(direction);
+                if !kind.can_be_pushed(direction) || !is_free(&to) {
+                    return;
+                }
+                while (kind == BlockKind::Ice || floor.is_ice(&to))
+                    && !floor.is_hole(&to)
+                    && is_free(&to.step(direction))
+                {
+                    to = to.step(direction);
+                }
+                to
+            }
+        };
+
+        if kind == BlockKind::Heavy
+            && !Self::is_second_push(&mut strain, was_pushing, entity, direction)
+        {
+            return;
+        }
+
+        push_history.0.push(Snapshot {
+            character: character_position,
+            blocks: (&entities, &blocks, &positions)
+                .join()
+                .filter(|(_entity, block, _position)| block.kind.is_pushable())
+                .map(|(entity, _block, position)| (entity, position.to_owned()))
+                .collect(),
+            removed: if kind == BlockKind::Breakable {
+                vec![BlockAndPosition {
+                    block,
+                    position: block_position,
+                }]
+            } else {
+                vec![]
+            },
+            floor: floor.to_owned(),
+            triggers: triggers.triggers.to_owned(),
+        });
+
+        if kind == BlockKind::Breakable {
+            debug!("Breaking block {:?} at {:?}", entity, from);
+
+            if let Some(block_handle) = block_handles.get(entity) {
+                if let Some(ref mut collision_world) = collision.world {
+                    collision_world.remove(&[block_handle.0]);
+                }
+            }
+            let _ = entities.delete(entity);
+        } else {
+            debug!("Pushing block {:?} from {:?} to {:?}", entity, from, to);
+
+            let position = Position(to.to_position());
+            if let Some(block_handle) = block_handles.get(entity) {
+                PhysicsSystem::set_block_handle_position(&mut collision, block_handle.0, &position);
+            }
+            let _ = positions.insert(entity, position);
+        }
+
+        push_events.0.push(PushEvent {
+            entity,
+            from,
+            to,
+            direction,
+        });
+    }
+}
+
+/// Puts the character, the pushable blocks, the floor and the triggers back the
+/// way they were before the last push, rebuilding the blocks that were removed.
+/// The doors follow their triggers on the next update. Returns false if there
+/// was nothing to undo.
+pub fn undo(world: &mut World, settings: &Settings) -> bool {
+    let snapshot = match world.write_resource::<PushHistory>().0.pop() {
+        Some(snapshot) => snapshot,
+        None => return false,
+    };
+
+    {
+        let block_handles = world.read_storage::<BlockHandle>();
+        let mut positions = world.write_storage::<Position>();
+        let mut collision = world.write_resource::<Collision>();
+
+        snapshot.blocks.into_iter().for_each(|(entity, position)| {
+            if let Some(block_handle) = block_handles.get(entity) {
+                PhysicsSystem::set_block_handle_position(&mut collision, block_handle.0, &position);
+            }
+            let _ = positions.insert(entity, position);
+        });
+    }
+
+    snapshot
+        .removed
+        .iter()
+        .for_each(|removed| map::create_block(world, settings, removed));
+    *world.write_resource::<Floor>() = snapshot.floor;
+    world
+        .write_resource::<Triggers>()
+        .restore(snapshot.triggers, &snapshot.character);
+    map::move_character(world, settings, &snapshot.character);
+
+    true
+}
@@ -0,0 +1,320 @@
+use character::Character;
+use floor::Floor;
+use map::{Block, BlockAndPosition, Collectible, CurrentLevel, FloorTile, Map, Stage, Trigger};
+use physics::Position;
+use score::Score;
+use serde::{Deserialize, Deserializer};
+use serde_json::{self, Value};
+use specs::{Join, World};
+use std::{fmt, io, time::Duration};
+use trigger::Triggers;
+use ScreenState;
+
+#[cfg(not(target_arch = "wasm32"))]
+use std::{fs, path::PathBuf};
+
+#[cfg(target_arch = "wasm32")]
+use stdweb::web::window;
+
+const SAVE_NAME: &str = "caudices_save";
+
+#[derive(Debug)]
+pub enum SaveError {
+    Io(io::Error),
+    Json(serde_json::Error),
+    Storage(String),
+}
+
+impl fmt::Display for SaveError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SaveError::Io(err) => write!(f, "{}", err),
+            SaveError::Json(err) => write!(f, "{}", err),
+            SaveError::Storage(reason) => write!(f, "{}", reason),
+        }
+    }
+}
+
+/// Somewhere to keep the save game between sessions.
+pub trait SaveStorage {
+    fn read(&self) -> Option<String>;
+    fn write(&self, contents: &str) -> Result<(), SaveError>;
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub struct FileStorage {
+    pub path: PathBuf,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl SaveStorage for FileStorage {
+    fn read(&self) -> Option<String> {
+        fs::read_to_string(&self.path).ok()
+    }
+
+    fn write(&self, contents: &str) -> Result<(), SaveError> {
+        fs::write(&self.path, contents).map_err(SaveError::Io)
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+pub struct LocalStorage {
+    pub key: String,
+}
+
+#[cfg(target_arch = "wasm32")]
+impl SaveStorage for LocalStorage {
+    fn read(&self) -> Option<String> {
+        window().local_storage().get(&self.key)
+    }
+
+    fn write(&self, contents: &str) -> Result<(), SaveError> {
+        window()
+            .local_storage()
+            .insert(&self.key, contents)
+            .map_err(|_| SaveError::Storage("Local storage is full".to_owned()))
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub fn default_storage() -> Box<dyn SaveStorage> {
+    Box::new(FileStorage {
+        path: PathBuf::from(format!("{}.json", SAVE_NAME)),
+    })
+}
+
+#[cfg(target_arch = "wasm32")]
+pub fn default_storage() -> Box<dyn SaveStorage> {
+    Box::new(LocalStorage {
+        key: SAVE_NAME.to_owned(),
+    })
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+pub struct LevelId {
+    pub stage: u16,
+    pub level: u16,
+}
+
+impl LevelId {
+    pub fn new(stage: u16, level: u16) -> Self {
+        LevelId { stage, level }
+    }
+
+    /// The level after this one, in stage and level order.
+    pub fn next(&self, stages: &[Stage]) -> Option<LevelId> {
+        let mut ids = stages
+            .iter()
+            .flat_map(|stage| {
+                stage
+                    .maps
+                    .iter()
+                    .map(move |map| LevelId::new(stage.stage, map.level))
+            })
+            .collect::<Vec<_>>();
+        ids.sort_by_key(|id| (id.stage, id.level));
+
+        ids.into_iter()
+            .find(|id| (id.stage, id.level) > (self.stage, self.level))
+    }
+}
+
+/// The score of a run that completed a level. Runs compare by their pushes
+/// first and their moves second.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Deserialize, Serialize)]
+pub struct BestRun {
+    pub pushes: u32,
+    pub steps: u32,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct LevelRecord {
+    pub id: LevelId,
+    pub completed: bool,
+    /// The shortest time the level has been completed in, in milliseconds.
+    pub best_time: Option<u64>,
+    #[serde(default)]
+    pub best_run: Option<BestRun>,
+}
+
+/// Where everything was in a level that was left before it was completed.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct LevelSnapshot {
+    pub id: LevelId,
+    /// In milliseconds.
+    pub time_elapsed: u64,
+    pub character: Position,
+    pub blocks: Vec<BlockAndPosition>,
+    /// Missing from the snapshots taken before maps had a floor.
+    #[serde(default)]
+    pub floor: Option<Vec<FloorTile>>,
+    /// Missing from the snapshots taken before maps had triggers.
+    #[serde(default)]
+    pub triggers: Option<Vec<Trigger>>,
+    /// The collectibles that haven't been picked up yet.
+    #[serde(default)]
+    pub collectibles: Option<Vec<Collectible>>,
+    /// The time the clocks that were picked up added, in milliseconds.
+    #[serde(default)]
+    pub bonus_time: u64,
+}
+
+impl LevelSnapshot {
+    /// Returns the map with the blocks, the character, the floor, the triggers
+    /// and the collectibles the way they were when the snapshot was taken.
+    pub fn apply(&self, map: &Map) -> Map {
+        Map {
+            start: self.character.to_owned(),
+            blocks_with_position: self.blocks.to_owned(),
+            floor: self
+                .floor
+                .to_owned()
+                .unwrap_or_else(|| map.floor.to_owned()),
+            triggers: self
+                .triggers
+                .to_owned()
+                .unwrap_or_else(|| map.triggers.to_owned()),
+            collectibles: self
+                .collectibles
+                .to_owned()
+                .unwrap_or_else(|| map.collectibles.to_owned()),
+            ..map.to_owned()
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct SaveGame {
+    pub screen_state: ScreenState,
+    pub unlocked: Vec<LevelId>,
+    pub records: Vec<LevelRecord>,
+    #[serde(default, deserialize_with = "de_snapshot")]
+    pub snapshot: Option<LevelSnapshot>,
+}
+
+/// A snapshot with blocks from an older map format can't be resumed, the level
+/// is started over instead of losing the whole save game.
+fn de_snapshot<'de, D>(deserializer: D) -> Result<Option<LevelSnapshot>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    Value::deserialize(deserializer).map(|snapshot| {
+        serde_json::from_value(snapshot)
+            .map_err(|err| warn!("Dropping the level snapshot: {}", err))
+            .ok()
+    })
+}
+
+impl Default for SaveGame {
+    fn default() -> Self {
+        SaveGame {
+            screen_state: ScreenState::default(),
+            unlocked: vec![LevelId::new(1, 1)],
+            records: vec![],
+            snapshot: None,
+        }
+    }
+}
+
+impl SaveGame {
+    pub fn is_unlocked(&self, id: LevelId) -> bool {
+        self.unlocked.contains(&id)
+    }
+
+    pub fn record(&self, id: LevelId) -> Option<&LevelRecord> {
+        self.records.iter().find(|record| record.id == id)
+    }
+
+    pub fn record_mut(&mut self, id: LevelId) -> &mut LevelRecord {
+        match self.records.iter().position(|record| record.id == id) {
+            Some(index) => &mut self.records[index],
+            None => {
+                self.records.push(LevelRecord {
+                    id,
+                    completed: false,
+                    best_time: None,
+                    best_run: None,
+                });
+                self.records.last_mut().unwrap()
+            }
+        }
+    }
+
+    /// Marks the level as completed, keeps the time and the score if they're the
+    /// best ones so far and unlocks the next level.
+    pub fn complete_level(&mut self, id: LevelId, time: u64, score: &Score, next: Option<LevelId>) {
+        {
+            let record = self.record_mut(id);
+            record.completed = true;
+            record.best_time = Some(record.best_time.map_or(time, |best| best.min(time)));
+            let run = BestRun {
+                pushes: score.pushes,
+                steps: score.steps,
+            };
+            record.best_run = Some(record.best_run.map_or(run, |best| best.min(run)));
+        }
+
+        if let Some(next) = next {
+            if !self.is_unlocked(next) {
+                info!("Unlocked stage {} level {}", next.stage, next.level);
+                self.unlocked.push(next);
+            }
+        }
+
+        self.snapshot = None;
+    }
+}
+
+/// Takes a snapshot of the level that is being played.
+pub fn snapshot(world: &World, time_elapsed: &Duration) -> Option<LevelSnapshot> {
+    let current_level = world.read_resource::<CurrentLevel>();
+    let level = current_level.0.as_ref()?;
+
+    let characters = world.read_storage::<Character>();
+    let blocks = world.read_storage::<Block>();
+    let positions = world.read_storage::<Position>();
+
+    let character = (&characters, &positions)
+        .join()
+        .next()
+        .map(|(_character, position)| position.to_owned())?;
+
+    Some(LevelSnapshot {
+        id: LevelId::new(level.stage, level.map.level),
+        time_elapsed: time_elapsed.as_secs() * 1000 + u64::from(time_elapsed.subsec_millis()),
+        character,
+        blocks: (&blocks, &positions)
+            .join()
+            .map(|(block, position)| BlockAndPosition {
+                block: block.to_owned(),
+                position: position.to_owned(),
+            })
+            .collect(),
+        floor: Some(world.read_resource::<Floor>().tiles()),
+        triggers: Some(world.read_resource::<Triggers>().triggers.to_owned()),
+        collectibles: Some(
+            world
+                .read_storage::<Collectible>()
+                .join()
+                .cloned()
+                .collect(),
+        ),
+        bonus_time: world.read_resource::<Score>().bonus_time,
+    })
+}
+
+pub fn load(storage: &dyn SaveStorage) -> SaveGame {
+    storage
+        .read()
+        .and_then(|contents| {
+            serde_json::from_str(&contents)
+                .map_err(|err| error!("Failed to read save game with error: {}", err))
+                .ok()
+        })
+        .unwrap_or_default()
+}
+
+pub fn store(storage: &dyn SaveStorage, save_game: &SaveGame) -> Result<(), SaveError> {
+    let contents = serde_json::to_string(save_game).map_err(SaveError::Json)?;
+    storage.write(&contents)
+}
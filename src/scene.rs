@@ -0,0 +1,76 @@
+use quicksilver::{lifecycle::Window, Result};
+use Game;
+
+/// What the scene stack should do after a scene has been updated.
+pub enum Transition {
+    None,
+    Push(Box<dyn Scene>),
+    Pop,
+    /// Pops every scene above the title screen.
+    PopToRoot,
+    /// Pops every scene above the title screen and pushes the given one.
+    SwitchTo(Box<dyn Scene>),
+}
+
+/// A screen of the game that handles its own input and drawing.
+pub trait Scene {
+    fn update(&mut self, game: &mut Game, window: &mut Window) -> Result<Transition>;
+
+    fn draw(&mut self, game: &mut Game, window: &mut Window) -> Result<()>;
+
+    /// Overlays are drawn on top of the scene below them instead of replacing it.
+    fn is_overlay(&self) -> bool {
+        false
+    }
+}
+
+/// The scenes that are open, the one on top being the one that gets the input.
+/// The first scene is never popped.
+pub struct SceneStack(Vec<Box<dyn Scene>>);
+
+impl SceneStack {
+    pub fn new(root: Box<dyn Scene>) -> Self {
+        SceneStack(vec![root])
+    }
+
+    fn apply(&mut self, transition: Transition) {
+        match transition {
+            Transition::None => {}
+            Transition::Push(scene) => self.0.push(scene),
+            Transition::Pop => {
+                if self.0.len() > 1 {
+                    self.0.pop();
+                }
+            }
+            Transition::PopToRoot => self.0.truncate(1),
+            Transition::SwitchTo(scene) => {
+                self.0.truncate(1);
+                self.0.push(scene);
+            }
+        }
+    }
+
+    pub fn update(&mut self, game: &mut Game, window: &mut Window) -> Result<()> {
+        let transition = match self.0.last_mut() {
+            Some(scene) => scene.update(game, window)?,
+            None => Transition::None,
+        };
+
+        self.apply(transition);
+        Ok(())
+    }
+
+    /// Draws the top scene along with the scenes below it that it's an overlay of.
+    pub fn draw(&mut self, game: &mut Game, window: &mut Window) -> Result<()> {
+        let first_drawn = self
+            .0
+            .iter()
+            .rposition(|scene| !scene.is_overlay())
+            .unwrap_or(0);
+
+        self.0[first_drawn..]
+            .iter_mut()
+            .map(|scene| scene.draw(game, window))
+            .collect()
+    }
+}
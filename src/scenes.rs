@@ -0,0 +1,433 @@
+use editor::{self, Editor, EditorAction};
+use gameplay::GameplayScene;
+use hint::Hints;
+use hud::{CachedText, TextPanel};
+use map::{self, CollectibleKind, CurrentLevel};
+use menu::Menu;
+use quicksilver::{
+    geom::{Rectangle, Shape, Vector},
+    graphics::{Background::Col, Color, FontStyle},
+    input::{ButtonState, Key},
+    lifecycle::Window,
+    Result,
+};
+use save::{LevelRecord, SaveGame};
+use scene::{Scene, Transition};
+use score::Score;
+use Game;
+use ScreenState;
+use WINDOW_HEIGHT;
+use WINDOW_WIDTH;
+
+const MIN_VELOCITY_CHANGE: f32 = 30.;
+const MAX_VELOCITY_CHANGE: f32 = 100.;
+const VELOCITY_CHANGE_STEP: f32 = 5.;
+
+fn is_pressed(window: &Window, key: Key) -> bool {
+    window.keyboard()[key] == ButtonState::Pressed
+}
+
+/// Dims whatever has been drawn so far, so overlays stand out from the level.
+fn draw_overlay(window: &mut Window) {
+    window.draw(
+        &Rectangle::new_sized((WINDOW_WIDTH, WINDOW_HEIGHT)),
+        Col(Color::BLACK.with_alpha(0.6)),
+    );
+}
+
+pub struct TitleScene {
+    text_panel: TextPanel,
+}
+
+impl TitleScene {
+    pub fn new() -> Self {
+        TitleScene {
+            text_panel: TextPanel::new(
+                "Caudices",
+                &["Enter to play", "L for levels", "S for settings"],
+            ),
+        }
+    }
+}
+
+impl Scene for TitleScene {
+    fn update(&mut self, game: &mut Game, window: &mut Window) -> Result<Transition> {
+        if is_pressed(window, Key::Return) {
+            Ok(Transition::Push(Box::new(GameplayScene::new())))
+        } else if is_pressed(window, Key::L) {
+            Ok(Transition::Push(Box::new(LevelSelectScene::new(game))))
+        } else if is_pressed(window, Key::S) {
+            Ok(Transition::Push(Box::new(SettingsScene::new())))
+        } else {
+            Ok(Transition::None)
+        }
+    }
+
+    fn draw(&mut self, game: &mut Game, window: &mut Window) -> Result<()> {
+        self.text_panel.draw(window, &game.game_asset.mali_font)
+    }
+}
+
+pub struct LevelSelectScene {
+    menu: Menu,
+}
+
+impl LevelSelectScene {
+    /// Opens the menu with the level that would be played next selected.
+    pub fn new(game: &Game) -> Self {
+        let id = game.current_level_id();
+        let selected = game
+            .menu_entries()
+            .iter()
+            .position(|entry| entry.id == id)
+            .unwrap_or(0);
+
+        LevelSelectScene {
+            menu: Menu::new(selected),
+        }
+    }
+}
+
+impl Scene for LevelSelectScene {
+    fn update(&mut self, game: &mut Game, window: &mut Window) -> Result<Transition> {
+        if is_pressed(window, Key::Escape) {
+            return Ok(Transition::Pop);
+        }
+
+        let entries = game.menu_entries();
+
+        match self.menu.handle_input(&entries, window) {
+            Some(id) => {
+                info!("Starting stage {} level {}", id.stage, id.level);
+                game.start_level(id);
+                Ok(Transition::SwitchTo(Box::new(GameplayScene::new())))
+            }
+            None => Ok(Transition::None),
+        }
+    }
+
+    fn draw(&mut self, game: &mut Game, window: &mut Window) -> Result<()> {
+        let entries = game.menu_entries();
+        self.menu.draw(&entries, window, &game.game_asset.mali_font)
+    }
+}
+
+pub struct PauseScene {
+    text_panel: TextPanel,
+}
+
+impl PauseScene {
+    pub fn new() -> Self {
+        PauseScene {
+            text_panel: TextPanel::new(
+                "Paused",
+                &["Enter to resume", "M for levels", "Q to quit to the title"],
+            ),
+        }
+    }
+}
+
+impl Scene for PauseScene {
+    fn update(&mut self, game: &mut Game, window: &mut Window) -> Result<Transition> {
+        if is_pressed(window, Key::Escape)
+            || is_pressed(window, Key::P)
+            || is_pressed(window, Key::Return)
+        {
+            Ok(Transition::Pop)
+        } else if is_pressed(window, Key::M) {
+            Ok(Transition::Push(Box::new(LevelSelectScene::new(game))))
+        } else if is_pressed(window, Key::Q) {
+            game.save_progress();
+            Ok(Transition::PopToRoot)
+        } else {
+            Ok(Transition::None)
+        }
+    }
+
+    fn draw(&mut self, game: &mut Game, window: &mut Window) -> Result<()> {
+        draw_overlay(window);
+        self.text_panel.draw(window, &game.game_asset.mali_font)
+    }
+
+    fn is_overlay(&self) -> bool {
+        true
+    }
+}
+
+pub struct GameOverScene {
+    text_panel: TextPanel,
+}
+
+impl GameOverScene {
+    pub fn new(reason: &str) -> Self {
+        GameOverScene {
+            text_panel: TextPanel::new("Game over", &[reason, "R to try again", "M for levels"]),
+        }
+    }
+}
+
+impl Scene for GameOverScene {
+    fn update(&mut self, game: &mut Game, window: &mut Window) -> Result<Transition> {
+        if is_pressed(window, Key::R) || is_pressed(window, Key::Return) {
+            info!("Restarting level");
+            game.restart_level();
+            Ok(Transition::Pop)
+        } else if is_pressed(window, Key::M) {
+            Ok(Transition::Push(Box::new(LevelSelectScene::new(game))))
+        } else {
+            Ok(Transition::None)
+        }
+    }
+
+    fn draw(&mut self, game: &mut Game, window: &mut Window) -> Result<()> {
+        draw_overlay(window);
+        self.text_panel.draw(window, &game.game_asset.mali_font)
+    }
+
+    fn is_overlay(&self) -> bool {
+        true
+    }
+}
+
+pub struct LevelCompleteScene {
+    text_panel: TextPanel,
+}
+
+impl LevelCompleteScene {
+    pub fn new() -> Self {
+        LevelCompleteScene {
+            text_panel: TextPanel::new("Level complete", &[]),
+        }
+    }
+
+    fn summary(
+        hints: &Hints,
+        score: &Score,
+        gems: usize,
+        record: Option<&LevelRecord>,
+    ) -> Vec<String> {
+        let mut summary = vec![
+            format!("Moves: {}  Pushes: {}", score.steps, score.pushes),
+            format!("Time left: {}s", score.time_left.unwrap_or(0) / 1000),
+        ];
+        if gems > 0 {
+            summary.push(format!("Gems: {}/{}", score.gems, gems));
+        }
+        if score.bonus_time > 0 {
+            summary.push(format!("Bonus time: {}s", score.bonus_time / 1000));
+        }
+        if score.cleared > 0 {
+            summary.push(format!("Cleared: {} blocks", score.cleared));
+        }
+        if let Some(record) = record {
+            if let Some(best_run) = record.best_run {
+                summary.push(format!(
+                    "Best: {} moves, {} pushes",
+                    best_run.steps, best_run.pushes
+                ));
+            }
+        }
+        summary.push(format!("Hints used: {}", hints.used));
+        summary.push("Enter to continue, M for levels".to_owned());
+
+        summary
+    }
+}
+
+impl Scene for LevelCompleteScene {
+    fn update(&mut self, game: &mut Game, window: &mut Window) -> Result<Transition> {
+        if is_pressed(window, Key::Return) {
+            let next = {
+                let save_game = game.world.read_resource::<SaveGame>();
+                save_game.screen_state.level_id()
+            };
+            info!("Starting stage {} level {}", next.stage, next.level);
+            game.start_level(next);
+            Ok(Transition::Pop)
+        } else if is_pressed(window, Key::M) {
+            Ok(Transition::Push(Box::new(LevelSelectScene::new(game))))
+        } else {
+            Ok(Transition::None)
+        }
+    }
+
+    fn draw(&mut self, game: &mut Game, window: &mut Window) -> Result<()> {
+        let id = game.current_level_id();
+        let summary = {
+            let hints = game.world.read_resource::<Hints>();
+            let score = game.world.read_resource::<Score>();
+            let save_game = game.world.read_resource::<SaveGame>();
+            let gems = game
+                .world
+                .read_resource::<CurrentLevel>()
+                .0
+                .as_ref()
+                .map_or(0, |level| {
+                    level
+                        .map
+                        .collectibles
+                        .iter()
+                        .filter(|collectible| collectible.kind == CollectibleKind::Gem)
+                        .count()
+                });
+            Self::summary(&hints, &score, gems, save_game.record(id))
+        };
+
+        self.text_panel.set_lines(&summary);
+
+        draw_overlay(window);
+        self.text_panel.draw(window, &game.game_asset.mali_font)
+    }
+
+    fn is_overlay(&self) -> bool {
+        true
+    }
+}
+
+pub struct SettingsScene {
+    selected: usize,
+    title: CachedText,
+    options: Vec<CachedText>,
+}
+
+impl SettingsScene {
+    const OPTIONS: usize = 2;
+
+    pub fn new() -> Self {
+        let option_style = FontStyle::new(32.0, Color::WHITE);
+
+        SettingsScene {
+            selected: 0,
+            title: CachedText::new("Settings", FontStyle::new(48.0, Color::WHITE)),
+            options: (0..Self::OPTIONS)
+                .map(|_index| CachedText::new("", option_style))
+                .collect(),
+        }
+    }
+
+    fn change(&self, game: &mut Game, is_increased: bool) {
+        let settings = &mut game.settings;
+
+        match self.selected {
+            0 => {
+                let step = if is_increased {
+                    VELOCITY_CHANGE_STEP
+                } else {
+                    -VELOCITY_CHANGE_STEP
+                };
+                settings.velocity_change = (settings.velocity_change + step)
+                    .max(MIN_VELOCITY_CHANGE)
+                    .min(MAX_VELOCITY_CHANGE);
+            }
+            _ => settings.show_deadlocks = !settings.show_deadlocks,
+        }
+    }
+}
+
+impl Scene for SettingsScene {
+    fn update(&mut self, game: &mut Game, window: &mut Window) -> Result<Transition> {
+        if is_pressed(window, Key::Escape) || is_pressed(window, Key::Return) {
+            return Ok(Transition::Pop);
+        }
+
+        if is_pressed(window, Key::Up) {
+            self.selected = self.selected.saturating_sub(1);
+        }
+        if is_pressed(window, Key::Down) {
+            self.selected = (self.selected + 1).min(Self::OPTIONS - 1);
+        }
+        if is_pressed(window, Key::Left) {
+            self.change(game, false);
+        }
+        if is_pressed(window, Key::Right) {
+            self.change(game, true);
+        }
+
+        Ok(Transition::None)
+    }
+
+    fn draw(&mut self, game: &mut Game, window: &mut Window) -> Result<()> {
+        let settings = &game.settings;
+        let selected = self.selected;
+
+        self.options[0].set_text(&format!("Character speed: {}", settings.velocity_change));
+        self.options[1].set_text(&format!(
+            "Deadlock warnings: {}",
+            if settings.show_deadlocks { "On" } else { "Off" }
+        ));
+
+        let font = &game.game_asset.mali_font;
+        self.title.draw(window, font, |_area| {
+            Vector::new(f32::from(WINDOW_WIDTH) / 2., 50.)
+        })?;
+
+        self.options
+            .iter_mut()
+            .enumerate()
+            .map(|(index, option)| {
+                let area = Rectangle::new(
+                    (50., 150. + index as f32 * 50.),
+                    (f32::from(WINDOW_WIDTH) - 100., 46.),
+                );
+
+                if index == selected {
+                    window.draw(&area, Col(Color::WHITE.with_alpha(0.2)));
+                }
+
+                option.draw(window, font, |_area| area.center())
+            })
+            .collect()
+    }
+}
+
+pub struct EditorScene {
+    editor: Editor,
+}
+
+impl EditorScene {
+    /// Opens the editor on the current map, if there is one.
+    pub fn for_current_map(game: &Game) -> Option<Self> {
+        let id = game.current_level_id();
+        let map = map::find_current_map(
+            map::stages_from_world(&game.world),
+            &game.world.read_resource::<ScreenState>(),
+        )?;
+
+        info!("Opening editor for stage {} level {}", id.stage, map.level);
+
+        Some(EditorScene {
+            editor: Editor::new(id.stage, map),
+        })
+    }
+}
+
+impl Scene for EditorScene {
+    fn update(&mut self, game: &mut Game, window: &mut Window) -> Result<Transition> {
+        match self.editor.handle_input(&game.settings, window) {
+            EditorAction::Edit => Ok(Transition::None),
+            EditorAction::TestPlay => match game.store_editor_map(&self.editor) {
+                Some(id) => {
+                    game.start_level(id);
+                    Ok(Transition::SwitchTo(Box::new(GameplayScene::new())))
+                }
+                None => Ok(Transition::None),
+            },
+            EditorAction::Save => {
+                if game.store_editor_map(&self.editor).is_some() {
+                    let stages = map::stages_from_world(&game.world);
+
+                    match editor::save_stages(&stages, &game.settings.stages_save_path) {
+                        Ok(()) => info!("Saved stages to {}", game.settings.stages_save_path),
+                        Err(err) => error!("Failed to save stages with error: {}", err),
+                    }
+                }
+                Ok(Transition::None)
+            }
+            EditorAction::Exit => Ok(Transition::Pop),
+        }
+    }
+
+    fn draw(&mut self, game: &mut Game, window: &mut Window) -> Result<()> {
+        self.editor.draw(window, &game.game_asset.mali_font)
+    }
+}
@@ -0,0 +1,216 @@
+use map::{MapError, Stage};
+use serde_json::{self, Value};
+
+/// The version of the stages file this game writes.
+pub const FORMAT_VERSION: u64 = 2;
+
+/// The whole stages file.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct StagesDocument {
+    pub format_version: u64,
+    pub stages: Vec<Stage>,
+}
+
+impl StagesDocument {
+    pub fn new(stages: Vec<Stage>) -> Self {
+        StagesDocument {
+            format_version: FORMAT_VERSION,
+            stages,
+        }
+    }
+}
+
+/// Upgrades a document by one version.
+type Migration = fn(Value) -> Value;
+
+/// The migrations in order, the first one upgrading version 0.
+const MIGRATIONS: [Migration; FORMAT_VERSION as usize] = [wrap_stages, block_kinds];
+
+/// Where the character was placed in every level before the maps had a start
+/// position.
+const LEGACY_START: (f32, f32) = (130., 330.);
+
+/// Version 0 was a bare list of stages, without a version, and its first maps
+/// had no start position.
+fn wrap_stages(mut stages: Value) -> Value {
+    if let Some(stages) = stages.as_array_mut() {
+        stages
+            .iter_mut()
+            .filter_map(|stage| stage.get_mut("maps").and_then(Value::as_array_mut))
+            .flat_map(|maps| maps.iter_mut())
+            .filter_map(Value::as_object_mut)
+            .filter(|map| !map.contains_key("start"))
+            .for_each(|map| {
+                let (x, y) = LEGACY_START;
+                map.insert("start".to_owned(), json!({"x": x, "y": y}));
+            });
+    }
+
+    json!({
+        "format_version": 1,
+        "stages": stages,
+    })
+}
+
+/// Version 1 only told fixed and movable blocks apart, with `can_be_moved`.
+fn block_kinds(mut document: Value) -> Value {
+    if let Some(stages) = document.get_mut("stages").and_then(Value::as_array_mut) {
+        stages
+            .iter_mut()
+            .filter_map(|stage| stage.get_mut("maps").and_then(Value::as_array_mut))
+            .flat_map(|maps| maps.iter_mut())
+            .filter_map(|map| map.get_mut("blocks").and_then(Value::as_array_mut))
+            .flat_map(|blocks| blocks.iter_mut())
+            .filter_map(Value::as_object_mut)
+            .for_each(|block| {
+                let can_be_moved = block
+                    .remove("can_be_moved")
+                    .and_then(|can_be_moved| can_be_moved.as_bool())
+                    .unwrap_or(false);
+                let kind = if can_be_moved { "movable" } else { "fixed" };
+                block.insert("kind".to_owned(), json!(kind));
+            });
+    }
+
+    if let Some(fields) = document.as_object_mut() {
+        fields.insert("format_version".to_owned(), json!(2));
+    }
+    document
+}
+
+/// The version of the document, where a bare list of stages is version 0.
+pub fn version(document: &Value) -> Result<u64, MapError> {
+    match document {
+        Value::Array(_) => Ok(0),
+        Value::Object(fields) => fields
+            .get("format_version")
+            .and_then(Value::as_u64)
+            .ok_or(MapError::MissingVersion),
+        _ => Err(MapError::MissingVersion),
+    }
+}
+
+/// Runs the migrations that bring the document from its version up to the
+/// current one.
+pub fn migrate(document: Value, version: u64) -> Result<Value, MapError> {
+    if version > FORMAT_VERSION {
+        return Err(MapError::UnsupportedVersion(version));
+    }
+
+    info!(
+        "Migrating the stages from format version {} to {}",
+        version, FORMAT_VERSION
+    );
+    Ok(MIGRATIONS
+        .iter()
+        .skip(version as usize)
+        .fold(document, |document, migration| migration(document)))
+}
+
+/// The stages as a document of the current version.
+pub fn to_json(stages: Vec<Stage>) -> serde_json::Result<String> {
+    serde_json::to_string_pretty(&StagesDocument::new(stages))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use map::{self, BlockKind};
+
+    fn stages_with_block(block: Value) -> Value {
+        json!([{
+            "stage": 1,
+            "maps": [{
+                "level": 1,
+                "blocks": [block],
+            }],
+        }])
+    }
+
+    fn block_kinds_of(document: &Value) -> Vec<Value> {
+        document["stages"][0]["maps"][0]["blocks"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|block| block["kind"].clone())
+            .collect()
+    }
+
+    #[test]
+    fn migrates_a_bare_list_of_stages() {
+        let document = stages_with_block(json!({"can_be_moved": true, "color": "red"}));
+        let version = version(&document).unwrap();
+        assert_eq!(version, 0);
+
+        let document = migrate(document, version).unwrap();
+        assert_eq!(document["format_version"], json!(FORMAT_VERSION));
+        assert_eq!(block_kinds_of(&document), vec![json!("movable")]);
+        assert!(document["stages"][0]["maps"][0]["blocks"][0]
+            .get("can_be_moved")
+            .is_none());
+    }
+
+    #[test]
+    fn migrates_blocks_that_can_be_moved_to_kinds() {
+        let mut document = json!({
+            "format_version": 1,
+            "stages": stages_with_block(json!({"can_be_moved": false, "color": "red"})),
+        });
+        document["stages"][0]["maps"][0]["blocks"]
+            .as_array_mut()
+            .unwrap()
+            .push(json!({"can_be_moved": true, "color": "blue"}));
+        let version = version(&document).unwrap();
+        assert_eq!(version, 1);
+
+        let document = migrate(document, version).unwrap();
+        assert_eq!(document["format_version"], json!(FORMAT_VERSION));
+        assert_eq!(
+            block_kinds_of(&document),
+            vec![json!("fixed"), json!("movable")]
+        );
+    }
+
+    #[test]
+    fn rejects_newer_versions() {
+        let document = json!({"format_version": 99, "stages": []});
+        let version = version(&document).unwrap();
+
+        match migrate(document, version) {
+            Err(MapError::UnsupportedVersion(99)) => (),
+            result => panic!("Expected an unsupported version, got {:?}", result),
+        }
+    }
+
+    #[test]
+    fn needs_a_version_on_documents() {
+        match version(&json!({"stages": []})) {
+            Err(MapError::MissingVersion) => (),
+            result => panic!("Expected a missing version, got {:?}", result),
+        }
+    }
+
+    #[test]
+    fn parses_the_stages_from_before_the_format_was_versioned() {
+        let stages = map::parse_json(include_bytes!("../tests/fixtures/stages_v0.json")).unwrap();
+        let maps = &stages[0].maps;
+        assert_eq!(maps.len(), 2);
+
+        assert!(maps
+            .iter()
+            .all(|map| (map.start.0.x, map.start.0.y) == LEGACY_START));
+
+        let movable_blocks = maps
+            .iter()
+            .map(|map| {
+                map.blocks_with_position
+                    .iter()
+                    .filter(|block_with_position| {
+                        block_with_position.block.kind == BlockKind::Movable
+                    })
+                    .count()
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(movable_blocks, vec![42, 1]);
+    }
+}
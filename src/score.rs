@@ -0,0 +1,48 @@
+use character::Character;
+use physics::Position;
+use push::PushEvents;
+use solver::Cell;
+use specs::{Join, Read, ReadStorage, System, Write};
+
+/// The score of the level that is being played.
+#[derive(Debug, Default)]
+pub struct Score {
+    /// Every time the character enters a new cell counts as a step.
+    pub steps: u32,
+    pub pushes: u32,
+    pub gems: u32,
+    /// The time the clocks that were picked up added, in milliseconds.
+    pub bonus_time: u64,
+    /// The blocks that were cleared by lining them up.
+    pub cleared: u32,
+    /// The time that was left when the level was completed, in milliseconds.
+    pub time_left: Option<u64>,
+    last_cell: Option<Cell>,
+}
+
+pub struct ScoreSystem;
+
+impl<'a> System<'a> for ScoreSystem {
+    type SystemData = (
+        Read<'a, PushEvents>,
+        ReadStorage<'a, Character>,
+        ReadStorage<'a, Position>,
+        Write<'a, Score>,
+    );
+
+    fn run(&mut self, (push_events, characters, positions, mut score): Self::SystemData) {
+        score.pushes += push_events.0.len() as u32;
+
+        let cell = (&characters, &positions)
+            .join()
+            .next()
+            .map(|(_character, position)| Cell::from_position(&position.0));
+
+        if let Some(cell) = cell {
+            if score.last_cell.map_or(false, |last_cell| last_cell != cell) {
+                score.steps += 1;
+            }
+            score.last_cell = Some(cell);
+        }
+    }
+}
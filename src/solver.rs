@@ -0,0 +1,996 @@
+use map::{BlockKind, FloorKind, Map, TriggerKind};
+use nalgebra::Vector2;
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap, HashSet, VecDeque};
+use std::fmt;
+use utils::palette_index;
+use HEADER_HEIGHT;
+use WINDOW_HEIGHT;
+use WINDOW_WIDTH;
+
+pub const GRID_SIZE: f32 = 50.;
+/// How many blocks of the same color have to be in a line to be cleared.
+pub const MATCH_LENGTH: usize = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Cell {
+    pub x: i32,
+    pub y: i32,
+}
+
+impl Cell {
+    pub fn new(x: i32, y: i32) -> Self {
+        Cell { x, y }
+    }
+
+    /// Returns the grid cell whose center is closest to the given position.
+    pub fn from_position(position: &Vector2<f32>) -> Self {
+        Cell::new(
+            (position.x / GRID_SIZE).round() as i32,
+            (position.y / GRID_SIZE).round() as i32,
+        )
+    }
+
+    pub fn to_position(&self) -> Vector2<f32> {
+        Vector2::new(self.x as f32 * GRID_SIZE, self.y as f32 * GRID_SIZE)
+    }
+
+    pub fn step(&self, direction: Direction) -> Cell {
+        let (x, y) = direction.offset();
+        Cell::new(self.x + x, self.y + y)
+    }
+
+    pub fn distance(&self, other: &Cell) -> usize {
+        ((self.x - other.x).abs() + (self.y - other.y).abs()) as usize
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Direction {
+    Up,
+    Down,
+    Left,
+    Right,
+}
+
+impl Direction {
+    pub const ALL: [Direction; 4] = [
+        Direction::Up,
+        Direction::Down,
+        Direction::Left,
+        Direction::Right,
+    ];
+
+    pub fn offset(&self) -> (i32, i32) {
+        match self {
+            Direction::Up => (0, -1),
+            Direction::Down => (0, 1),
+            Direction::Left => (-1, 0),
+            Direction::Right => (1, 0),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Move {
+    pub direction: Direction,
+    pub push: bool,
+}
+
+/// The cells the character and the blocks can be in, inclusive.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Bounds {
+    pub min: Cell,
+    pub max: Cell,
+}
+
+impl Bounds {
+    pub fn contains(&self, cell: &Cell) -> bool {
+        cell.x >= self.min.x && cell.x <= self.max.x && cell.y >= self.min.y && cell.y <= self.max.y
+    }
+}
+
+impl Default for Bounds {
+    fn default() -> Self {
+        let grid_size = GRID_SIZE as u16;
+
+        Bounds {
+            min: Cell::new(1, (HEADER_HEIGHT / GRID_SIZE) as i32),
+            max: Cell::new(
+                (WINDOW_WIDTH / grid_size - 1).into(),
+                (WINDOW_HEIGHT / grid_size - 1).into(),
+            ),
+        }
+    }
+}
+
+/// The parts of a map that never change while playing it. Conveyors and enemies
+/// move over time, which the solver doesn't take into account. Doors are kept by
+/// their cell and triggers along with the id of the door they open, and every
+/// teleporter along with its partner. Colored goals are kept with the palette
+/// index of the blocks they take. When matches are cleared, a level can also be
+/// solved by clearing the blocks instead of pushing them onto the goals. With
+/// gravity, the board is seen from the side.
+#[derive(Debug, Clone)]
+pub struct Board {
+    pub bounds: Bounds,
+    pub walls: HashSet<Cell>,
+    pub goals: HashSet<Cell>,
+    pub goal_colors: HashMap<Cell, usize>,
+    pub ice: HashSet<Cell>,
+    pub doors: HashMap<Cell, u16>,
+    pub triggers: Vec<(Cell, TriggerKind, u16)>,
+    pub teleporters: HashMap<Cell, Cell>,
+    pub clears_matches: bool,
+    pub has_gravity: bool,
+    dead_cells: HashSet<Cell>,
+}
+
+/// The parts of a map that change while playing it, every block but the fixed
+/// ones along with the palette index of its color, the holes that haven't been
+/// filled and the switches that are on and keys that have been taken. These are
+/// kept sorted so that equal layouts compare and hash the same.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct State {
+    pub character: Cell,
+    pub blocks: Vec<(Cell, BlockKind, usize)>,
+    pub holes: Vec<Cell>,
+    pub active: Vec<Cell>,
+}
+
+impl State {
+    pub fn new(
+        character: Cell,
+        mut blocks: Vec<(Cell, BlockKind, usize)>,
+        mut holes: Vec<Cell>,
+        mut active: Vec<Cell>,
+    ) -> Self {
+        blocks.sort();
+        holes.sort();
+        active.sort();
+        State {
+            character,
+            blocks,
+            holes,
+            active,
+        }
+    }
+
+    pub fn from_map(map: &Map) -> Self {
+        let blocks = map
+            .blocks_with_position
+            .iter()
+            .filter(|block_with_position| block_with_position.block.kind != BlockKind::Fixed)
+            .map(|block_with_position| {
+                (
+                    Cell::from_position(&block_with_position.position.0),
+                    block_with_position.block.kind,
+                    palette_index(&block_with_position.block.color),
+                )
+            })
+            .collect();
+        let holes = map
+            .floor
+            .iter()
+            .filter(|tile| tile.kind == FloorKind::Hole)
+            .map(|tile| Cell::from_position(&tile.position.0))
+            .collect();
+        let active = map
+            .triggers
+            .iter()
+            .filter(|trigger| trigger.active && trigger.kind != TriggerKind::PressurePlate)
+            .map(|trigger| Cell::from_position(&trigger.position.0))
+            .collect();
+
+        State::new(Cell::from_position(&map.start.0), blocks, holes, active)
+    }
+
+    pub fn block(&self, cell: &Cell) -> Option<BlockKind> {
+        self.painted_block(cell).map(|(kind, _paint)| kind)
+    }
+
+    /// The kind of the block in the given cell along with the palette index of
+    /// its color.
+    fn painted_block(&self, cell: &Cell) -> Option<(BlockKind, usize)> {
+        self.blocks
+            .binary_search_by_key(cell, |(block, _kind, _paint)| *block)
+            .ok()
+            .map(|index| (self.blocks[index].1, self.blocks[index].2))
+    }
+
+    pub fn has_block(&self, cell: &Cell) -> bool {
+        self.block(cell).is_some()
+    }
+
+    pub fn has_hole(&self, cell: &Cell) -> bool {
+        self.holes.binary_search(cell).is_ok()
+    }
+
+    pub fn is_active(&self, cell: &Cell) -> bool {
+        self.active.binary_search(cell).is_ok()
+    }
+
+    fn has_pushable_block(&self, cell: &Cell) -> bool {
+        self.block(cell).map_or(false, BlockKind::is_pushable)
+    }
+
+    /// The blocks that have to end up on the goals, with the palette index of
+    /// their color.
+    pub fn pushable_blocks<'a>(
+        &'a self,
+    ) -> impl Iterator<Item = (&'a Cell, BlockKind, usize)> + 'a {
+        self.blocks
+            .iter()
+            .filter(|(_block, kind, _paint)| kind.is_pushable())
+            .map(|(block, kind, paint)| (block, *kind, *paint))
+    }
+}
+
+/// The cells of the pushable blocks that are in a row or a column of at least
+/// `MATCH_LENGTH` blocks of the same color.
+pub fn matched_blocks(blocks: &[(Cell, BlockKind, usize)]) -> Vec<Cell> {
+    let paints = blocks
+        .iter()
+        .filter(|(_block, kind, _paint)| kind.is_pushable())
+        .map(|(block, _kind, paint)| (*block, *paint))
+        .collect::<HashMap<_, _>>();
+    let run_length = |block: &Cell, paint: usize, direction: Direction| {
+        let mut length = 0;
+        let mut next = block.step(direction);
+        while paints.get(&next) == Some(&paint) {
+            length += 1;
+            next = next.step(direction);
+        }
+        length
+    };
+
+    let mut matched = paints
+        .iter()
+        .filter(|(block, paint)| {
+            [
+                (Direction::Left, Direction::Right),
+                (Direction::Up, Direction::Down),
+            ]
+            .iter()
+            .any(|(back, forward)| {
+                1 + run_length(block, **paint, *back) + run_length(block, **paint, *forward)
+                    >= MATCH_LENGTH
+            })
+        })
+        .map(|(block, _paint)| *block)
+        .collect::<Vec<_>>();
+    matched.sort();
+    matched
+}
+
+impl Board {
+    /// The dead cells assume blocks move a cell at a time, which isn't true on
+    /// ice, so boards with ice don't have any. The holes count as places a block
+    /// can be pushed to.
+    pub fn new(
+        bounds: Bounds,
+        walls: HashSet<Cell>,
+        goals: HashSet<Cell>,
+        ice: HashSet<Cell>,
+        holes: &[Cell],
+    ) -> Self {
+        let mut board = Board {
+            bounds,
+            walls,
+            goals,
+            goal_colors: HashMap::new(),
+            ice,
+            doors: HashMap::new(),
+            triggers: vec![],
+            teleporters: HashMap::new(),
+            clears_matches: false,
+            has_gravity: false,
+            dead_cells: HashSet::new(),
+        };
+        if board.ice.is_empty() {
+            board.dead_cells = board.find_dead_cells(holes);
+        }
+        board
+    }
+
+    pub fn from_map(map: &Map, bounds: Bounds) -> Self {
+        let walls = map
+            .blocks_with_position
+            .iter()
+            .filter(|block_with_position| block_with_position.block.kind == BlockKind::Fixed)
+            .map(|block_with_position| Cell::from_position(&block_with_position.position.0))
+            .collect();
+        let goals = map
+            .goals
+            .iter()
+            .map(|goal| Cell::from_position(&goal.position.0))
+            .collect();
+        let goal_colors = map
+            .goals
+            .iter()
+            .filter_map(|goal| {
+                goal.paint()
+                    .map(|paint| (Cell::from_position(&goal.position.0), paint))
+            })
+            .collect();
+        let ice = map
+            .floor
+            .iter()
+            .filter(|tile| tile.kind == FloorKind::Ice)
+            .map(|tile| Cell::from_position(&tile.position.0))
+            .collect();
+
+        let doors = map
+            .doors
+            .iter()
+            .map(|door| (Cell::from_position(&door.position.0), door.id))
+            .collect();
+        let triggers = map
+            .triggers
+            .iter()
+            .map(|trigger| {
+                (
+                    Cell::from_position(&trigger.position.0),
+                    trigger.kind,
+                    trigger.door,
+                )
+            })
+            .collect();
+
+        let mut board = Board {
+            goal_colors,
+            doors,
+            triggers,
+            teleporters: map.teleporter_partners(),
+            clears_matches: map.clear_matches,
+            has_gravity: map.gravity,
+            ..Board::new(bounds, walls, goals, ice, &State::from_map(map).holes)
+        };
+        // Teleporters get blocks out of the cells the dead cells assume they're
+        // stuck in, cleared blocks don't have to get out at all and falling
+        // blocks move in ways pushes can't.
+        if !board.teleporters.is_empty() || board.clears_matches || board.has_gravity {
+            board.dead_cells.clear();
+        }
+        board
+    }
+
+    /// Whether the given cell is a goal a block with the given palette index
+    /// can be left on.
+    pub fn accepts(&self, cell: &Cell, paint: usize) -> bool {
+        self.goals.contains(cell)
+            && self
+                .goal_colors
+                .get(cell)
+                .map_or(true, |goal_paint| *goal_paint == paint)
+    }
+
+    pub fn is_free(&self, cell: &Cell) -> bool {
+        self.bounds.contains(cell) && !self.walls.contains(cell)
+    }
+
+    /// A door is open while one of its pressure plates has something on it, one
+    /// of its switches is on or one of its keys has been taken.
+    pub fn is_door_open(&self, state: &State, id: u16) -> bool {
+        self.triggers.iter().any(|(cell, kind, door)| {
+            *door == id
+                && match kind {
+                    TriggerKind::PressurePlate => state.character == *cell || state.has_block(cell),
+                    TriggerKind::Switch | TriggerKind::Key => state.is_active(cell),
+                }
+        })
+    }
+
+    fn is_closed_door(&self, state: &State, cell: &Cell) -> bool {
+        self.doors
+            .get(cell)
+            .map_or(false, |id| !self.is_door_open(state, *id))
+    }
+
+    /// Where something that lands in the given cell ends up, which is the
+    /// partner of the teleporter in it if nothing is there. A block can't be
+    /// teleported onto the character.
+    fn teleport(&self, state: &State, cell: Cell, character: Option<Cell>) -> Cell {
+        match self.teleporters.get(&cell) {
+            Some(partner) if Some(*partner) != character && !self.is_blocked(state, partner) => {
+                *partner
+            }
+            _ => cell,
+        }
+    }
+
+    /// The switches and keys that are active once the character has stepped
+    /// into the given cell.
+    fn step_on(&self, state: &State, cell: Cell) -> Vec<Cell> {
+        let mut active = state.active.to_owned();
+
+        match self
+            .triggers
+            .iter()
+            .find(|(trigger, _kind, _door)| *trigger == cell)
+        {
+            Some((_, TriggerKind::Switch, _)) => match active.binary_search(&cell) {
+                Ok(index) => {
+                    active.remove(index);
+                }
+                Err(index) => active.insert(index, cell),
+            },
+            Some((_, TriggerKind::Key, _)) => {
+                if let Err(index) = active.binary_search(&cell) {
+                    active.insert(index, cell);
+                }
+            }
+            _ => {}
+        }
+
+        active
+    }
+
+    pub fn is_solved(&self, state: &State) -> bool {
+        state
+            .pushable_blocks()
+            .all(|(block, _kind, paint)| self.accepts(block, paint))
+    }
+
+    /// Moves the thing in the given cell further while it's on ice, or all the
+    /// way for ice blocks, until it's about to hit something. It stops in a hole.
+    fn slide(&self, state: &State, from: Cell, direction: Direction, is_ice_block: bool) -> Cell {
+        let mut cell = from;
+
+        while (is_ice_block || self.ice.contains(&cell))
+            && !state.has_hole(&cell)
+            && !self.is_blocked(state, &cell.step(direction))
+        {
+            cell = cell.step(direction);
+        }
+
+        cell
+    }
+
+    /// Moves the character one cell in the given direction like `walk` does.
+    /// With gravity, the character can only walk sideways and push blocks
+    /// sideways, or jump up a cell off whatever it's standing on. Afterwards the
+    /// blocks and the character fall until they land on something, except for
+    /// the character right after a jump, as it can still move onto a ledge.
+    pub fn step(&self, state: &State, direction: Direction) -> Option<(State, Move)> {
+        if !self.has_gravity {
+            return self.walk(state, direction);
+        }
+
+        let is_jump = direction == Direction::Up;
+        let can_jump = self.is_blocked(state, &state.character.step(Direction::Down))
+            && !state.has_block(&state.character.step(Direction::Up));
+        if direction == Direction::Down || (is_jump && !can_jump) {
+            return None;
+        }
+
+        let (next, solver_move) = self.walk(state, direction)?;
+        self.fall(next, is_jump).map(|next| (next, solver_move))
+    }
+
+    /// Drops every movable block onto whatever is below it, filling the holes
+    /// it falls into and clearing the lines it lands in, and the character
+    /// unless it's jumping. Returns `None` if the character falls into a hole.
+    fn fall(&self, mut state: State, is_jumping: bool) -> Option<State> {
+        loop {
+            let falling = state.blocks.iter().position(|(block, kind, _paint)| {
+                let below = block.step(Direction::Down);
+                kind.is_pushable() && below != state.character && !self.is_blocked(&state, &below)
+            });
+
+            if let Some(index) = falling {
+                let below = state.blocks[index].0.step(Direction::Down);
+                let mut blocks = state.blocks.to_owned();
+                let mut holes = state.holes.to_owned();
+                if state.has_hole(&below) {
+                    blocks.remove(index);
+                    holes.retain(|hole| *hole != below);
+                } else {
+                    blocks[index].0 = below;
+                }
+
+                state = State::new(state.character, blocks, holes, state.active);
+                continue;
+            }
+
+            if self.clears_matches {
+                let matched = matched_blocks(&state.blocks);
+                if !matched.is_empty() {
+                    state
+                        .blocks
+                        .retain(|(block, _kind, _paint)| !matched.contains(block));
+                    continue;
+                }
+            }
+
+            let below = state.character.step(Direction::Down);
+            if is_jumping || self.is_blocked(&state, &below) {
+                return Some(state);
+            }
+            if state.has_hole(&below) {
+                return None;
+            }
+
+            let active = self.step_on(&state, below);
+            state = State {
+                character: below,
+                active,
+                ..state
+            };
+        }
+    }
+
+    /// Moves the character one cell in the given direction, pushing the block in
+    /// front of it if there's room behind the block. Ice blocks slide as far as
+    /// they can and breakable blocks crumble, leaving the character where it was.
+    /// Anything on an ice floor keeps sliding and a block pushed into a hole
+    /// fills it, while the character can't walk into one. Closed doors are
+    /// walls, and the character works the trigger in the cell it ends up in.
+    /// Teleporters move the character or the pushed block to their partner.
+    /// Blocks that end up in a line of the same color are cleared when the
+    /// board clears matches.
+    fn walk(&self, state: &State, direction: Direction) -> Option<(State, Move)> {
+        let next = state.character.step(direction);
+
+        if !self.is_free(&next) || state.has_hole(&next) || self.is_closed_door(state, &next) {
+            return None;
+        }
+
+        let (kind, paint) = match state.painted_block(&next) {
+            Some(block) => block,
+            None => {
+                let character = self.slide(state, next, direction, false);
+                let character = self.teleport(state, character, None);
+                if state.has_hole(&character) {
+                    return None;
+                }
+
+                return Some((
+                    State::new(
+                        character,
+                        state.blocks.to_owned(),
+                        state.holes.to_owned(),
+                        self.step_on(state, character),
+                    ),
+                    Move {
+                        direction,
+                        push: false,
+                    },
+                ));
+            }
+        };
+
+        let push = Move {
+            direction,
+            push: true,
+        };
+
+        if kind == BlockKind::Breakable {
+            let blocks = state
+                .blocks
+                .iter()
+                .filter(|(block, _kind, _paint)| *block != next)
+                .cloned()
+                .collect();
+
+            return Some((
+                State::new(
+                    state.character,
+                    blocks,
+                    state.holes.to_owned(),
+                    state.active.to_owned(),
+                ),
+                push,
+            ));
+        }
+
+        if !kind.can_be_pushed(direction) {
+            return None;
+        }
+
+        let behind = next.step(direction);
+        if self.is_blocked(state, &behind) {
+            return None;
+        }
+        let behind = self.slide(state, behind, direction, kind == BlockKind::Ice);
+        let behind = self.teleport(state, behind, Some(next));
+
+        let mut blocks = state
+            .blocks
+            .iter()
+            .filter(|(block, _kind, _paint)| *block != next)
+            .cloned()
+            .collect::<Vec<_>>();
+        let holes = if state.has_hole(&behind) {
+            state
+                .holes
+                .iter()
+                .filter(|hole| **hole != behind)
+                .cloned()
+                .collect()
+        } else {
+            blocks.push((behind, kind, paint));
+            state.holes.to_owned()
+        };
+        if self.clears_matches {
+            let matched = matched_blocks(&blocks);
+            blocks.retain(|(block, _kind, _paint)| !matched.contains(block));
+        }
+
+        Some((
+            State::new(next, blocks, holes, self.step_on(state, next)),
+            push,
+        ))
+    }
+
+    /// Finds the cells a block can never be pushed out of onto a goal or into a
+    /// hole, by pulling blocks backwards from every goal and hole.
+    fn find_dead_cells(&self, holes: &[Cell]) -> HashSet<Cell> {
+        let mut live_cells = HashSet::new();
+        let mut queue = self
+            .goals
+            .iter()
+            .chain(holes.iter())
+            .cloned()
+            .collect::<VecDeque<Cell>>();
+
+        while let Some(cell) = queue.pop_front() {
+            if !live_cells.insert(cell) {
+                continue;
+            }
+
+            Direction::ALL.iter().for_each(|direction| {
+                let block_cell = cell.step(*direction);
+                let character_cell = block_cell.step(*direction);
+
+                if self.is_free(&block_cell)
+                    && self.is_free(&character_cell)
+                    && !live_cells.contains(&block_cell)
+                {
+                    queue.push_back(block_cell);
+                }
+            });
+        }
+
+        (self.bounds.min.x..=self.bounds.max.x)
+            .flat_map(|x| (self.bounds.min.y..=self.bounds.max.y).map(move |y| Cell::new(x, y)))
+            .filter(|cell| self.is_free(cell) && !live_cells.contains(cell))
+            .collect()
+    }
+
+    fn is_blocked(&self, state: &State, cell: &Cell) -> bool {
+        !self.is_free(cell) || state.has_block(cell) || self.is_closed_door(state, cell)
+    }
+
+    /// Checks whether the block in the given cell can never reach a goal again,
+    /// either because it's on a dead cell or because it's frozen in a 2x2 square
+    /// of blocks and walls that isn't entirely on goals. Ice blocks don't stop
+    /// where the dead cells assume, so only frozen squares count for them. No
+    /// block is ever stuck when matches are cleared, as it can still be cleared
+    /// where it is, or with gravity, as it can still fall out of the square.
+    pub fn is_block_deadlocked(
+        &self,
+        state: &State,
+        cell: &Cell,
+        kind: BlockKind,
+        paint: usize,
+    ) -> bool {
+        if self.clears_matches || self.has_gravity {
+            return false;
+        }
+        if self.accepts(cell, paint) || kind == BlockKind::Ice {
+            return self.is_frozen_square(state, cell);
+        }
+
+        self.dead_cells.contains(cell) || self.is_frozen_square(state, cell)
+    }
+
+    /// Breakable blocks can still be cleared away, so only walls and the blocks
+    /// that are pushed around freeze a square. A block on a goal of another
+    /// color doesn't count as done.
+    fn is_frozen_square(&self, state: &State, cell: &Cell) -> bool {
+        [(-1, -1), (-1, 0), (0, -1), (0, 0)].iter().any(|(x, y)| {
+            let square = [
+                Cell::new(cell.x + x, cell.y + y),
+                Cell::new(cell.x + x + 1, cell.y + y),
+                Cell::new(cell.x + x, cell.y + y + 1),
+                Cell::new(cell.x + x + 1, cell.y + y + 1),
+            ];
+
+            square.iter().all(|square_cell| {
+                !self.is_free(square_cell) || state.has_pushable_block(square_cell)
+            }) && square.iter().any(|square_cell| {
+                state
+                    .painted_block(square_cell)
+                    .map_or(false, |(kind, paint)| {
+                        kind.is_pushable() && !self.accepts(square_cell, paint)
+                    })
+            })
+        })
+    }
+
+    pub fn deadlocked_blocks(&self, state: &State) -> Vec<Cell> {
+        state
+            .pushable_blocks()
+            .filter(|(block, kind, paint)| self.is_block_deadlocked(state, block, *kind, *paint))
+            .map(|(block, _kind, _paint)| *block)
+            .collect()
+    }
+
+    /// A lower bound of the moves left when nothing slides, every block needs at
+    /// least one push per cell to its closest goal of its color or hole. With
+    /// gravity, blocks fall for free, so only the cells across count.
+    fn estimate(&self, state: &State) -> usize {
+        state
+            .pushable_blocks()
+            .map(|(block, _kind, paint)| {
+                self.goals
+                    .iter()
+                    .filter(|goal| self.accepts(goal, paint))
+                    .chain(state.holes.iter())
+                    .map(|goal| {
+                        if self.has_gravity {
+                            (block.x - goal.x).abs() as usize
+                        } else {
+                            block.distance(goal)
+                        }
+                    })
+                    .min()
+                    .unwrap_or(0)
+            })
+            .sum()
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum SolverError {
+    NotEnoughGoals { blocks: usize, goals: usize },
+    Unsolvable,
+    StateLimitReached(usize),
+}
+
+impl fmt::Display for SolverError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SolverError::NotEnoughGoals { blocks, goals } => write!(
+                f,
+                "There are {} movable blocks but only {} goals",
+                blocks, goals
+            ),
+            SolverError::Unsolvable => write!(f, "The map can't be solved"),
+            SolverError::StateLimitReached(limit) => {
+                write!(f, "Gave up after searching {} states", limit)
+            }
+        }
+    }
+}
+
+/// Searches for the shortest sequence of moves that puts every movable block on a
+/// goal, using A* over the character and block positions. States where a block
+/// is deadlocked are never expanded.
+pub fn solve(board: &Board, state: &State, max_states: usize) -> Result<Vec<Move>, SolverError> {
+    let pushable_blocks = state.pushable_blocks().count();
+    if !board.clears_matches && board.goals.len() + state.holes.len() < pushable_blocks {
+        return Err(SolverError::NotEnoughGoals {
+            blocks: pushable_blocks,
+            goals: board.goals.len(),
+        });
+    }
+
+    let mut nodes: Vec<(State, Option<(usize, Move)>)> = vec![(state.to_owned(), None)];
+    let mut costs = HashMap::new();
+    let mut queue = BinaryHeap::new();
+
+    costs.insert(state.to_owned(), 0);
+    queue.push(Reverse((board.estimate(state), 0, 0)));
+
+    let mut expanded = 0;
+
+    while let Some(Reverse((_estimate, cost, index))) = queue.pop() {
+        let current = nodes[index].0.to_owned();
+
+        if costs.get(&current).map_or(false, |best| *best < cost) {
+            continue;
+        }
+
+        if board.is_solved(&current) {
+            let mut moves = vec![];
+            let mut parent = nodes[index].1;
+
+            while let Some((parent_index, parent_move)) = parent {
+                moves.push(parent_move);
+                parent = nodes[parent_index].1;
+            }
+
+            moves.reverse();
+            return Ok(moves);
+        }
+
+        expanded += 1;
+        if expanded > max_states {
+            return Err(SolverError::StateLimitReached(max_states));
+        }
+
+        Direction::ALL.iter().for_each(|direction| {
+            if let Some((next, next_move)) = board.step(&current, *direction) {
+                if next_move.push && !board.deadlocked_blocks(&next).is_empty() {
+                    return;
+                }
+
+                let next_cost = cost + 1;
+                let is_better = costs.get(&next).map_or(true, |best| next_cost < *best);
+
+                if is_better {
+                    let estimate = next_cost + board.estimate(&next);
+                    costs.insert(next.to_owned(), next_cost);
+                    nodes.push((next, Some((index, next_move))));
+                    queue.push(Reverse((estimate, next_cost, nodes.len() - 1)));
+                }
+            }
+        });
+    }
+
+    Err(SolverError::Unsolvable)
+}
+
+pub fn solve_map(map: &Map, max_states: usize) -> Result<Vec<Move>, SolverError> {
+    let board = Board::from_map(map, Bounds::default());
+    solve(&board, &State::from_map(map), max_states)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MAX_STATES: usize = 10_000;
+
+    fn board(max: Cell, walls: &[Cell], goals: &[Cell]) -> Board {
+        Board::new(
+            Bounds {
+                min: Cell::new(0, 0),
+                max,
+            },
+            walls.iter().cloned().collect(),
+            goals.iter().cloned().collect(),
+            HashSet::new(),
+            &[],
+        )
+    }
+
+    fn movable(cell: Cell) -> (Cell, BlockKind, usize) {
+        (cell, BlockKind::Movable, 0)
+    }
+
+    #[test]
+    fn solves_a_straight_push() {
+        let board = board(Cell::new(4, 0), &[], &[Cell::new(3, 0)]);
+        let state = State::new(
+            Cell::new(0, 0),
+            vec![movable(Cell::new(1, 0))],
+            vec![],
+            vec![],
+        );
+
+        let moves = solve(&board, &state, MAX_STATES).unwrap();
+
+        assert_eq!(
+            moves,
+            vec![
+                Move {
+                    direction: Direction::Right,
+                    push: true,
+                };
+                2
+            ]
+        );
+    }
+
+    #[test]
+    fn block_in_a_corner_is_deadlocked() {
+        let board = board(Cell::new(2, 2), &[], &[Cell::new(2, 2)]);
+        let state = State::new(
+            Cell::new(1, 1),
+            vec![movable(Cell::new(0, 0))],
+            vec![],
+            vec![],
+        );
+
+        assert_eq!(board.deadlocked_blocks(&state), vec![Cell::new(0, 0)]);
+        assert_eq!(
+            solve(&board, &state, MAX_STATES),
+            Err(SolverError::Unsolvable)
+        );
+    }
+
+    #[test]
+    fn frozen_square_is_deadlocked() {
+        let board = board(Cell::new(4, 4), &[], &[Cell::new(4, 4)]);
+        let blocks = [(1, 1), (2, 1), (1, 2), (2, 2)]
+            .iter()
+            .map(|(x, y)| movable(Cell::new(*x, *y)))
+            .collect();
+        let state = State::new(Cell::new(0, 0), blocks, vec![], vec![]);
+
+        assert_eq!(board.deadlocked_blocks(&state).len(), 4);
+    }
+
+    #[test]
+    fn needs_a_goal_for_every_block() {
+        let board = board(Cell::new(4, 4), &[], &[Cell::new(3, 3)]);
+        let state = State::new(
+            Cell::new(0, 0),
+            vec![movable(Cell::new(2, 2)), movable(Cell::new(2, 3))],
+            vec![],
+            vec![],
+        );
+
+        assert_eq!(
+            solve(&board, &state, MAX_STATES),
+            Err(SolverError::NotEnoughGoals {
+                blocks: 2,
+                goals: 1,
+            })
+        );
+    }
+
+    #[test]
+    fn matches_lines_of_three_or_more() {
+        let row = |length: i32, paint: usize| {
+            (0..length)
+                .map(|x| (Cell::new(x, paint as i32), BlockKind::Movable, paint))
+                .collect::<Vec<_>>()
+        };
+
+        assert!(matched_blocks(&row(2, 0)).is_empty());
+        assert_eq!(matched_blocks(&row(3, 0)).len(), 3);
+        assert_eq!(matched_blocks(&row(4, 0)).len(), 4);
+
+        let mut mixed = row(2, 0);
+        mixed.push((Cell::new(2, 0), BlockKind::Movable, 1));
+        assert!(matched_blocks(&mixed).is_empty());
+
+        let column = (0..3)
+            .map(|y| (Cell::new(0, y), BlockKind::Movable, 0))
+            .collect::<Vec<_>>();
+        assert_eq!(matched_blocks(&column).len(), 3);
+    }
+
+    #[test]
+    fn teleporter_moves_the_character_to_its_partner() {
+        let mut board = board(Cell::new(4, 0), &[], &[]);
+        board.teleporters.insert(Cell::new(1, 0), Cell::new(3, 0));
+        board.teleporters.insert(Cell::new(3, 0), Cell::new(1, 0));
+        let state = State::new(Cell::new(0, 0), vec![], vec![], vec![]);
+
+        let (next, _move) = board.step(&state, Direction::Right).unwrap();
+
+        assert_eq!(next.character, Cell::new(3, 0));
+    }
+
+    #[test]
+    fn gravity_jumps_onto_ledges_and_drops_blocks() {
+        // A wall with a block on top of it, seen from the side.
+        let board = Board {
+            has_gravity: true,
+            ..board(Cell::new(3, 2), &[Cell::new(1, 2)], &[])
+        };
+        let state = State::new(
+            Cell::new(0, 2),
+            vec![movable(Cell::new(1, 1))],
+            vec![],
+            vec![],
+        );
+
+        assert!(board.step(&state, Direction::Right).is_none());
+        assert!(board.step(&state, Direction::Down).is_none());
+
+        let (jumped, _move) = board.step(&state, Direction::Up).unwrap();
+        assert_eq!(jumped.character, Cell::new(0, 1));
+        assert!(board.step(&jumped, Direction::Up).is_none());
+
+        let (pushed, _move) = board.step(&jumped, Direction::Right).unwrap();
+        assert_eq!(pushed.character, Cell::new(1, 1));
+        assert_eq!(pushed.blocks, vec![movable(Cell::new(2, 2))]);
+    }
+}
@@ -0,0 +1,149 @@
+use character::Character;
+use collision::{BlockHandle, Collision};
+use map::{CurrentLevel, Map};
+use physics::{PhysicsSystem, Position};
+use push::PushEvents;
+use solver::{Board, Cell};
+use specs::{Entities, Join, Read, ReadStorage, System, Write, WriteStorage};
+use std::collections::HashMap;
+use trigger::Triggers;
+
+/// The teleporters of the level that is being played, each with its partner.
+#[derive(Debug, Default)]
+pub struct Teleporters {
+    pub partners: HashMap<Cell, Cell>,
+    /// The cell the character was in during the last update, so that it's only
+    /// teleported when it steps onto a teleporter and not right back after
+    /// arriving.
+    character_cell: Option<Cell>,
+}
+
+impl Teleporters {
+    pub fn from_map(map: &Map) -> Self {
+        Teleporters {
+            partners: map.teleporter_partners(),
+            character_cell: None,
+        }
+    }
+
+    /// Keeps the character from being teleported away from where it's put.
+    pub fn place_character(&mut self, position: &Position) {
+        self.character_cell = Some(Cell::from_position(&position.0));
+    }
+}
+
+/// Moves the character when it steps onto a teleporter and the blocks that are
+/// pushed onto one to the partner of the teleporter, along with their collision
+/// objects, if nothing is there.
+pub struct TeleportSystem;
+
+impl TeleportSystem {
+    fn is_free(
+        board: &Board,
+        triggers: &Triggers,
+        positions: &WriteStorage<Position>,
+        cell: &Cell,
+    ) -> bool {
+        board.is_free(cell)
+            && !triggers.is_closed(cell)
+            && !positions
+                .join()
+                .any(|position| Cell::from_position(&position.0) == *cell)
+    }
+}
+
+impl<'a> System<'a> for TeleportSystem {
+    type SystemData = (
+        Entities<'a>,
+        Read<'a, CurrentLevel>,
+        Read<'a, Triggers>,
+        ReadStorage<'a, Character>,
+        ReadStorage<'a, BlockHandle>,
+        WriteStorage<'a, Position>,
+        Write<'a, Collision>,
+        Write<'a, PushEvents>,
+        Write<'a, Teleporters>,
+    );
+
+    fn run(
+        &mut self,
+        (
+            entities,
+            current_level,
+            triggers,
+            characters,
+            block_handles,
+            mut positions,
+            mut collision,
+            mut push_events,
+            mut teleporters,
+        ): Self::SystemData,
+    ) {
+        let board = match current_level.0 {
+            Some(ref level) => &level.board,
+            None => return,
+        };
+        if teleporters.partners.is_empty() {
+            return;
+        }
+
+        // Broken blocks stay where they were and are gone.
+        push_events
+            .0
+            .iter_mut()
+            .filter(|event| event.from != event.to)
+            .for_each(|event| {
+                let partner = match teleporters.partners.get(&event.to) {
+                    Some(partner) => *partner,
+                    None => return,
+                };
+                if !Self::is_free(board, &triggers, &positions, &partner) {
+                    return;
+                }
+
+                debug!(
+                    "Teleporting block {:?} from {:?} to {:?}",
+                    event.entity, event.to, partner
+                );
+                let position = Position(partner.to_position());
+                if let Some(block_handle) = block_handles.get(event.entity) {
+                    PhysicsSystem::set_block_handle_position(
+                        &mut collision,
+                        block_handle.0,
+                        &position,
+                    );
+                }
+                let _ = positions.insert(event.entity, position);
+                event.to = partner;
+            });
+
+        let character = (&entities, &characters, &positions)
+            .join()
+            .next()
+            .map(|(entity, _character, position)| (entity, Cell::from_position(&position.0)));
+        let (entity, cell) = match character {
+            Some(character) => character,
+            None => return,
+        };
+
+        let has_stepped = teleporters.character_cell != Some(cell);
+        teleporters.character_cell = Some(cell);
+
+        let partner = match teleporters.partners.get(&cell) {
+            Some(partner) if has_stepped => *partner,
+            _ => return,
+        };
+        if !Self::is_free(board, &triggers, &positions, &partner) {
+            return;
+        }
+
+        info!("Teleporting the character from {:?} to {:?}", cell, partner);
+        let position = Position(partner.to_position());
+        collision.set_character_position(&position);
+        if let Some(character_handle) = collision.character_handle {
+            PhysicsSystem::set_block_handle_position(&mut collision, character_handle, &position);
+        }
+        let _ = positions.insert(entity, position);
+        teleporters.character_cell = Some(partner);
+    }
+}
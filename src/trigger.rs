@@ -0,0 +1,179 @@
+use character::Character;
+use collision::Collision;
+use map::{Block, BlockKind, Door, Map, Trigger, TriggerKind};
+use ncollide2d::world::CollisionObjectHandle;
+use physics::{PhysicsSystem, Position};
+use solver::{Cell, GRID_SIZE};
+use specs::{Join, ReadExpect, ReadStorage, System, World, Write};
+use BlockSize;
+use Settings;
+
+/// A door of the level that is being played, with its collision object while
+/// it's closed.
+#[derive(Debug, Clone)]
+pub struct DoorState {
+    pub door: Door,
+    pub is_open: bool,
+    handle: Option<CollisionObjectHandle>,
+}
+
+impl DoorState {
+    pub fn cell(&self) -> Cell {
+        Cell::from_position(&self.door.position.0)
+    }
+}
+
+/// The doors and the triggers of the level that is being played.
+#[derive(Debug, Default)]
+pub struct Triggers {
+    pub doors: Vec<DoorState>,
+    pub triggers: Vec<Trigger>,
+    /// The cell the character was in during the last update, so that a switch
+    /// only flips when the character steps onto it.
+    character_cell: Option<Cell>,
+}
+
+impl Triggers {
+    /// The cells of the switches that are on and the keys that have been taken.
+    pub fn active_cells(&self) -> Vec<Cell> {
+        self.triggers
+            .iter()
+            .filter(|trigger| trigger.active && trigger.kind != TriggerKind::PressurePlate)
+            .map(|trigger| Cell::from_position(&trigger.position.0))
+            .collect()
+    }
+
+    pub fn is_closed(&self, cell: &Cell) -> bool {
+        self.doors
+            .iter()
+            .any(|door| !door.is_open && door.cell() == *cell)
+    }
+
+    /// Puts the switches and keys back the way they were, with the character
+    /// standing at the given position.
+    pub fn restore(&mut self, triggers: Vec<Trigger>, character: &Position) {
+        self.triggers = triggers;
+        self.character_cell = Some(Cell::from_position(&character.0));
+    }
+}
+
+/// Replaces the doors of the previous level with the closed doors of the given
+/// map. The ones whose triggers are active open on the next update.
+pub fn create_doors(world: &mut World, settings: &Settings, map: &Map) {
+    let mut triggers = world.write_resource::<Triggers>();
+    let mut collision = world.write_resource::<Collision>();
+
+    let handles = triggers
+        .doors
+        .iter()
+        .filter_map(|door| door.handle)
+        .collect::<Vec<_>>();
+    if let Some(ref mut collision_world) = collision.world {
+        collision_world.remove(&handles);
+    }
+
+    triggers.doors = map
+        .doors
+        .iter()
+        .map(|door| DoorState {
+            door: door.to_owned(),
+            is_open: false,
+            handle: PhysicsSystem::add_block_handle(settings, &mut collision, &door.position),
+        })
+        .collect();
+    triggers.restore(map.triggers.to_owned(), &map.start);
+}
+
+/// Works the triggers the character and the blocks are on and opens or closes
+/// the doors linked to them, adding and removing the doors' collision objects.
+/// A door that should close stays open while something is in its way.
+pub struct TriggerSystem;
+
+impl<'a> System<'a> for TriggerSystem {
+    type SystemData = (
+        ReadExpect<'a, BlockSize>,
+        ReadStorage<'a, Character>,
+        ReadStorage<'a, Block>,
+        ReadStorage<'a, Position>,
+        Write<'a, Collision>,
+        Write<'a, Triggers>,
+    );
+
+    fn run(
+        &mut self,
+        (block_size, characters, blocks, positions, mut collision, mut triggers): Self::SystemData,
+    ) {
+        let (character, character_position) = match (&characters, &positions).join().next() {
+            Some((character, position)) => (character, position.0),
+            None => return,
+        };
+        let character_cell = Cell::from_position(&character_position);
+        let block_cells = (&blocks, &positions)
+            .join()
+            .filter(|(block, _position)| block.kind != BlockKind::Fixed)
+            .map(|(_block, position)| Cell::from_position(&position.0))
+            .collect::<Vec<_>>();
+
+        let has_stepped = triggers.character_cell != Some(character_cell);
+        triggers.character_cell = Some(character_cell);
+
+        triggers.triggers.iter_mut().for_each(|trigger| {
+            let cell = Cell::from_position(&trigger.position.0);
+
+            match trigger.kind {
+                TriggerKind::PressurePlate => {
+                    trigger.active = cell == character_cell || block_cells.contains(&cell);
+                }
+                TriggerKind::Switch if has_stepped && cell == character_cell => {
+                    trigger.active = !trigger.active;
+                    let state = if trigger.active { "on" } else { "off" };
+                    info!("Switch for door {} turned {}", trigger.door, state);
+                }
+                TriggerKind::Key if !trigger.active && cell == character_cell => {
+                    trigger.active = true;
+                    info!("Took the key for door {}", trigger.door);
+                }
+                TriggerKind::Switch | TriggerKind::Key => {}
+            }
+        });
+
+        let open_doors = triggers
+            .triggers
+            .iter()
+            .filter(|trigger| trigger.active)
+            .map(|trigger| trigger.door)
+            .collect::<Vec<_>>();
+
+        triggers.doors.iter_mut().for_each(|door| {
+            let should_open = open_doors.contains(&door.door.id);
+            if should_open == door.is_open {
+                return;
+            }
+
+            if should_open {
+                info!("Opening door {}", door.door.id);
+                if let Some(handle) = door.handle.take() {
+                    if let Some(ref mut collision_world) = collision.world {
+                        collision_world.remove(&[handle]);
+                    }
+                }
+                door.is_open = true;
+            } else {
+                let offset = door.door.position.0 - character_position;
+                let is_character_in_way = offset.x.abs() < (GRID_SIZE + character.width) / 2.
+                    && offset.y.abs() < (GRID_SIZE + character.height) / 2.;
+                if is_character_in_way || block_cells.contains(&door.cell()) {
+                    return;
+                }
+
+                info!("Closing door {}", door.door.id);
+                door.handle = PhysicsSystem::add_rect_handle(
+                    block_size.0.x,
+                    &mut collision,
+                    &door.door.position,
+                );
+                door.is_open = false;
+            }
+        });
+    }
+}
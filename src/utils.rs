@@ -1,5 +1,16 @@
 use quicksilver::graphics::Color;
 use serde::de::{self, Deserialize, Deserializer, Unexpected};
+use serde::ser::{self, Serializer};
+
+/// The colors that can be used for blocks in the map files, by name.
+pub const COLORS: [(&str, Color); 6] = [
+    ("red", Color::RED),
+    ("green", Color::GREEN),
+    ("blue", Color::BLUE),
+    ("orange", Color::ORANGE),
+    ("purple", Color::PURPLE),
+    ("indigo", Color::INDIGO),
+];
 
 pub fn de_color<'de, D>(deserializer: D) -> Result<Color, D::Error>
 where
@@ -7,16 +18,46 @@ where
 {
     let color_string = String::deserialize(deserializer)?.to_lowercase();
 
-    match color_string.as_ref() {
-        "red" => Ok(Color::RED),
-        "green" => Ok(Color::GREEN),
-        "blue" => Ok(Color::BLUE),
-        "orange" => Ok(Color::ORANGE),
-        "purple" => Ok(Color::PURPLE),
-        "indigo" => Ok(Color::INDIGO),
-        _ => Err(de::Error::invalid_value(
-            Unexpected::Str("Color not found"),
-            &"0",
-        )),
+    COLORS
+        .iter()
+        .find(|(name, _color)| *name == color_string)
+        .map(|(_name, color)| *color)
+        .ok_or_else(|| de::Error::invalid_value(Unexpected::Str("Color not found"), &"0"))
+}
+
+pub fn se_color<S>(color: &Color, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    COLORS
+        .iter()
+        .find(|(_name, palette_color)| palette_color == color)
+        .ok_or_else(|| ser::Error::custom(format!("Color {:?} isn't in the palette", color)))
+        .and_then(|(name, _color)| serializer.serialize_str(name))
+}
+
+/// The index of the color in the palette. Colors outside of it all share the
+/// index past its end.
+pub fn palette_index(color: &Color) -> usize {
+    COLORS
+        .iter()
+        .position(|(_name, palette_color)| palette_color == color)
+        .unwrap_or_else(|| COLORS.len())
+}
+
+pub fn de_optional_color<'de, D>(deserializer: D) -> Result<Option<Color>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    de_color(deserializer).map(Some)
+}
+
+pub fn se_optional_color<S>(color: &Option<Color>, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    match color {
+        Some(color) => se_color(color, serializer),
+        None => serializer.serialize_none(),
     }
 }
@@ -0,0 +1,89 @@
+#[cfg(not(target_arch = "wasm32"))]
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+/// How many updates to wait between looking at the file.
+#[cfg(not(target_arch = "wasm32"))]
+const CHECK_INTERVAL: u32 = 60;
+
+/// Watches the stages file so changes to it show up without restarting the game.
+#[cfg(not(target_arch = "wasm32"))]
+pub struct StagesWatcher {
+    path: PathBuf,
+    modified: Option<SystemTime>,
+    ticks: u32,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl StagesWatcher {
+    pub fn new(path: &str) -> Self {
+        let path = PathBuf::from(path);
+        let modified = Self::modified(&path);
+
+        info!("Watching {} for changes", path.display());
+        StagesWatcher {
+            path,
+            modified,
+            ticks: 0,
+        }
+    }
+
+    fn modified(path: &Path) -> Option<SystemTime> {
+        fs::metadata(path)
+            .and_then(|metadata| metadata.modified())
+            .ok()
+    }
+
+    pub fn path(&self) -> String {
+        self.path.display().to_string()
+    }
+
+    /// Returns the contents of the file when it has changed since it was last
+    /// looked at.
+    pub fn poll(&mut self) -> Option<Vec<u8>> {
+        self.ticks = (self.ticks + 1) % CHECK_INTERVAL;
+        if self.ticks != 0 {
+            return None;
+        }
+
+        let modified = Self::modified(&self.path);
+        if modified.is_none() || modified == self.modified {
+            return None;
+        }
+        self.modified = modified;
+
+        match fs::read(&self.path) {
+            Ok(stages_bytes) => Some(stages_bytes),
+            Err(err) => {
+                error!("Couldn't read {}: {}", self.path.display(), err);
+                None
+            }
+        }
+    }
+}
+
+/// The browser build can't see the stages file, so there's nothing to watch.
+#[cfg(target_arch = "wasm32")]
+pub struct StagesWatcher {
+    path: String,
+}
+
+#[cfg(target_arch = "wasm32")]
+impl StagesWatcher {
+    pub fn new(path: &str) -> Self {
+        StagesWatcher {
+            path: path.to_owned(),
+        }
+    }
+
+    pub fn path(&self) -> String {
+        self.path.to_owned()
+    }
+
+    pub fn poll(&mut self) -> Option<Vec<u8>> {
+        None
+    }
+}